@@ -1,5 +1,6 @@
 use crate::listen_addr::{ListenAddr, ListenAddress};
-use crate::rpc::config::OutboundRateLimiterConfig;
+use crate::rpc::config::{InboundRateLimiterConfig, OutboundRateLimiterConfig};
+use crate::rpc::NetworkParams;
 use crate::types::GossipKind;
 use crate::{Enr, PeerIdSerialized};
 use directory::{
@@ -140,8 +141,20 @@ pub struct Config {
     /// Whether light client protocols should be enabled.
     pub enable_light_client_server: bool,
 
+    /// Configuration for the inbound rate limiter (requests made by our peers).
+    pub inbound_rate_limiter_config: InboundRateLimiterConfig,
+
     /// Configuration for the outbound rate limiter (requests made by this node).
     pub outbound_rate_limiter_config: Option<OutboundRateLimiterConfig>,
+
+    /// The time an RPC substream awaiting a response chunk is kept open before it times out.
+    pub resp_timeout: Duration,
+
+    /// The time after which the first response chunk to an RPC request is logged as slow.
+    pub slow_response_threshold: Duration,
+
+    /// The time an inbound RPC substream has to negotiate a protocol and deliver its request.
+    pub inbound_negotiation_timeout: Duration,
 }
 
 impl Config {
@@ -261,6 +274,8 @@ impl Default for Config {
             .build()
             .expect("valid gossipsub configuration");
 
+        let rpc_params = NetworkParams::default();
+
         // Discv5 Unsolicited Packet Rate Limiter
         let filter_rate_limiter = Some(
             discv5::RateLimiterBuilder::new()
@@ -324,7 +339,11 @@ impl Default for Config {
             topics: Vec::new(),
             metrics_enabled: false,
             enable_light_client_server: false,
+            inbound_rate_limiter_config: InboundRateLimiterConfig::default(),
             outbound_rate_limiter_config: None,
+            resp_timeout: rpc_params.resp_timeout,
+            slow_response_threshold: rpc_params.slow_response_threshold,
+            inbound_negotiation_timeout: rpc_params.inbound_negotiation_timeout,
         }
     }
 }
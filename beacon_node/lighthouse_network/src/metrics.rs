@@ -69,6 +69,27 @@ lazy_static! {
         "RPC requests total",
         &["type"]
     );
+    pub static ref TOTAL_RPC_REQUESTS_SENT: Result<IntCounterVec> = try_create_int_counter_vec(
+        "libp2p_rpc_requests_sent_total",
+        "RPC requests sent to peers, per protocol",
+        &["protocol"]
+    );
+    pub static ref TOTAL_RPC_RESPONSES_RECEIVED: Result<IntCounterVec> =
+        try_create_int_counter_vec(
+            "libp2p_rpc_responses_received_total",
+            "RPC response chunks received from peers, per protocol",
+            &["protocol"]
+        );
+    pub static ref RPC_RESPONSE_TIME: Result<HistogramVec> = try_create_histogram_vec(
+        "libp2p_rpc_response_time_seconds",
+        "Time between queueing an RPC request and receiving the first chunk of its response",
+        &["protocol"]
+    );
+    pub static ref TOTAL_RPC_GOODBYES: Result<IntCounterVec> = try_create_int_counter_vec(
+        "libp2p_rpc_goodbyes_total",
+        "Goodbye messages sent to and received from peers, per reason",
+        &["reason", "direction"]
+    );
     pub static ref PEER_ACTION_EVENTS_PER_CLIENT: Result<IntCounterVec> =
         try_create_int_counter_vec(
             "libp2p_peer_actions_per_client",
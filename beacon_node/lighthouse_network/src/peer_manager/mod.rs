@@ -504,10 +504,13 @@ impl<TSpec: EthSpec> PeerManager<TSpec> {
                 return;
             }
             RPCError::HandlerRejected => PeerAction::Fatal,
-            RPCError::InvalidData(_) => {
+            RPCError::InvalidData(_) | RPCError::SSZDecodeError(_) => match direction {
                 // Peer is not complying with the protocol. This is considered a malicious action
-                PeerAction::Fatal
-            }
+                ConnectionDirection::Outgoing => PeerAction::Fatal,
+                // The peer sent a request we could not decode. A single malformed request is
+                // tolerated, repeated ones get the peer banned.
+                ConnectionDirection::Incoming => PeerAction::LowToleranceError,
+            },
             RPCError::IoError(_e) => {
                 // this could their fault or ours, so we tolerate this
                 PeerAction::HighToleranceError
@@ -549,7 +552,6 @@ impl<TSpec: EthSpec> PeerManager<TSpec> {
                     Protocol::Status => PeerAction::LowToleranceError,
                 },
             },
-            RPCError::SSZDecodeError(_) => PeerAction::Fatal,
             RPCError::UnsupportedProtocol => {
                 // Not supporting a protocol shouldn't be considered a malicious action, but
                 // it is an action that in some cases will make the peer unfit to continue
@@ -1391,6 +1393,36 @@ mod tests {
         assert_eq!(peer_manager.network_globals.connected_or_dialing_peers(), 3);
     }
 
+    /// A request from a peer that we cannot decode is penalised, but does not get the peer
+    /// banned. An invalid response to our own request does.
+    #[tokio::test]
+    async fn test_peer_manager_invalid_data_penalty_depends_on_direction() {
+        let mut peer_manager = build_peer_manager(3).await;
+        let inbound_peer = PeerId::random();
+        let outbound_peer = PeerId::random();
+        peer_manager.inject_connect_ingoing(&inbound_peer, "/ip4/0.0.0.0".parse().unwrap(), None);
+        peer_manager.inject_connect_ingoing(&outbound_peer, "/ip4/0.0.0.0".parse().unwrap(), None);
+
+        let error = RPCError::InvalidData("malformed".into());
+        peer_manager.handle_rpc_error(
+            &inbound_peer,
+            Protocol::BlocksByRange,
+            &error,
+            ConnectionDirection::Incoming,
+        );
+        peer_manager.handle_rpc_error(
+            &outbound_peer,
+            Protocol::BlocksByRange,
+            &error,
+            ConnectionDirection::Outgoing,
+        );
+
+        let peers = peer_manager.network_globals.peers.read();
+        assert!(peers.is_connected(&inbound_peer));
+        assert!(peers.score(&inbound_peer) < 0.0);
+        assert!(!peers.is_connected(&outbound_peer));
+    }
+
     #[tokio::test]
     async fn test_peer_manager_not_enough_outbound_peers_no_panic_during_heartbeat() {
         let mut peer_manager = build_peer_manager(20).await;
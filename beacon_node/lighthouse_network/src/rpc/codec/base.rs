@@ -1,7 +1,7 @@
 //! This handles the various supported encoding mechanism for the Eth 2.0 RPC.
 
 use crate::rpc::methods::ErrorType;
-use crate::rpc::{InboundRequest, OutboundRequest, RPCCodedResponse, RPCResponse};
+use crate::rpc::{InboundRequest, OutboundRequest, RPCCodedResponse, RPCError, RPCResponse};
 use libp2p::bytes::BufMut;
 use libp2p::bytes::BytesMut;
 use std::marker::PhantomData;
@@ -136,7 +136,7 @@ impl<TCodec, TSpec> Decoder for BaseOutboundCodec<TCodec, TSpec>
 where
     TSpec: EthSpec,
     TCodec: OutboundCodec<OutboundRequest<TSpec>, CodecErrorType = ErrorType>
-        + Decoder<Item = RPCResponse<TSpec>>,
+        + Decoder<Item = RPCResponse<TSpec>, Error = RPCError>,
 {
     type Item = RPCCodedResponse<TSpec>;
     type Error = <TCodec as Decoder>::Error;
@@ -175,12 +175,23 @@ where
         // return the result
         inner_result
     }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode(src)? {
+            Some(response) => Ok(Some(response)),
+            // the stream closed cleanly between chunks
+            None if src.is_empty() && self.current_response_code.is_none() => Ok(None),
+            // the stream closed part way through a chunk
+            None => Err(RPCError::IncompleteStream),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::super::ssz_snappy::*;
     use super::*;
+    use crate::rpc::methods::RPCResponseErrorCode;
     use crate::rpc::protocol::*;
 
     use std::sync::Arc;
@@ -263,7 +274,7 @@ mod tests {
 
         assert_eq!(
             snappy_decoded_message,
-            RPCError::IoError("input bytes exceed maximum".to_string()),
+            RPCError::InvalidData("Invalid length-prefix: input bytes exceed maximum".to_string()),
             "length-prefix of > 10 bytes is invalid"
         );
     }
@@ -327,4 +338,163 @@ mod tests {
             RPCError::InvalidData(_)
         ));
     }
+
+    #[test]
+    fn test_encode_then_decode_error_response() {
+        let snappy_protocol_id =
+            ProtocolId::new(Protocol::Status, Version::V1, Encoding::SSZSnappy);
+        let fork_context = Arc::new(fork_context(ForkName::Base));
+        let max_rpc_size = max_rpc_size(&fork_context);
+
+        // A non-ascii message, to check the error string survives as UTF-8.
+        let message = "Ошибка сервера ✗ 服务器错误";
+
+        for error_code in [
+            RPCResponseErrorCode::InvalidRequest,
+            RPCResponseErrorCode::ServerError,
+            RPCResponseErrorCode::ResourceUnavailable,
+            RPCResponseErrorCode::RateLimited,
+        ] {
+            let mut inbound_codec = BaseInboundCodec::new(SSZSnappyInboundCodec::<Spec>::new(
+                snappy_protocol_id.clone(),
+                max_rpc_size,
+                fork_context.clone(),
+            ));
+            let mut buf = BytesMut::new();
+            inbound_codec
+                .encode(
+                    RPCCodedResponse::Error(error_code, message.into()),
+                    &mut buf,
+                )
+                .unwrap();
+
+            // The chunk starts with the error code byte
+            assert_eq!(
+                buf[0],
+                RPCCodedResponse::<Spec>::Error(error_code, message.into())
+                    .as_u8()
+                    .unwrap()
+            );
+
+            let mut outbound_codec = BaseOutboundCodec::new(SSZSnappyOutboundCodec::<Spec>::new(
+                snappy_protocol_id.clone(),
+                max_rpc_size,
+                fork_context.clone(),
+            ));
+            match outbound_codec.decode(&mut buf).unwrap() {
+                Some(RPCCodedResponse::Error(decoded_code, decoded_message)) => {
+                    assert_eq!(decoded_code, error_code);
+                    assert_eq!(decoded_message.to_string(), message);
+                }
+                other => panic!("Expected an error response, got {:?}", other),
+            }
+            assert!(buf.is_empty());
+        }
+    }
+
+    /// Test that a chunk with an unknown response code is decoded as an `Unknown` error, keeping
+    /// the rest of the chunk as the error message.
+    #[test]
+    fn test_decode_unknown_response_code() {
+        let snappy_protocol_id =
+            ProtocolId::new(Protocol::Status, Version::V1, Encoding::SSZSnappy);
+        let fork_context = Arc::new(fork_context(ForkName::Base));
+        let max_rpc_size = max_rpc_size(&fork_context);
+        let message = "Unexpected failure";
+
+        let mut inbound_codec = BaseInboundCodec::new(SSZSnappyInboundCodec::<Spec>::new(
+            snappy_protocol_id.clone(),
+            max_rpc_size,
+            fork_context.clone(),
+        ));
+        let mut buf = BytesMut::new();
+        inbound_codec
+            .encode(
+                RPCCodedResponse::Error(RPCResponseErrorCode::ServerError, message.into()),
+                &mut buf,
+            )
+            .unwrap();
+        // Replace the response code with one that is not defined.
+        buf[0] = 42;
+
+        let mut outbound_codec = BaseOutboundCodec::new(SSZSnappyOutboundCodec::<Spec>::new(
+            snappy_protocol_id,
+            max_rpc_size,
+            fork_context,
+        ));
+        match outbound_codec.decode(&mut buf).unwrap() {
+            Some(RPCCodedResponse::Error(code, decoded_message)) => {
+                assert_eq!(code, RPCResponseErrorCode::Unknown);
+                assert_eq!(decoded_message.to_string(), message);
+            }
+            other => panic!("Expected an error response, got {:?}", other),
+        }
+        assert!(buf.is_empty());
+    }
+
+    /// Test that the inputs of a probing peer (no bytes, a single byte or a length prefix of zero)
+    /// are handled without panicking.
+    #[test]
+    fn test_decode_short_inputs() {
+        let snappy_protocol_id =
+            ProtocolId::new(Protocol::Status, Version::V1, Encoding::SSZSnappy);
+        let fork_context = Arc::new(fork_context(ForkName::Base));
+        let max_rpc_size = max_rpc_size(&fork_context);
+        let new_outbound_codec = || {
+            BaseOutboundCodec::new(SSZSnappyOutboundCodec::<Spec>::new(
+                snappy_protocol_id.clone(),
+                max_rpc_size,
+                fork_context.clone(),
+            ))
+        };
+        let mut inbound_codec = BaseInboundCodec::new(SSZSnappyInboundCodec::<Spec>::new(
+            snappy_protocol_id.clone(),
+            max_rpc_size,
+            fork_context.clone(),
+        ));
+
+        // An empty stream is a no-op in both directions.
+        assert!(matches!(
+            inbound_codec.decode(&mut BytesMut::new()),
+            Ok(None)
+        ));
+        assert!(matches!(
+            new_outbound_codec().decode_eof(&mut BytesMut::new()),
+            Ok(None)
+        ));
+
+        // A lone response code waits for the rest of the chunk, and is incomplete if the stream
+        // ends there.
+        for code in [0u8, 1, 2, 3, 42] {
+            let mut codec = new_outbound_codec();
+            let mut buf = BytesMut::from(&[code][..]);
+            assert!(matches!(codec.decode(&mut buf), Ok(None)));
+            assert!(matches!(
+                codec.decode_eof(&mut buf),
+                Err(RPCError::IncompleteStream)
+            ));
+        }
+
+        // A zero length prefix is out of bounds for a `StatusMessage`, whether it is a request or
+        // a response.
+        assert!(matches!(
+            inbound_codec.decode(&mut BytesMut::from(&[0u8][..])),
+            Err(RPCError::InvalidData(_))
+        ));
+        assert!(matches!(
+            new_outbound_codec().decode(&mut BytesMut::from(&[0u8, 0][..])),
+            Err(RPCError::InvalidData(_))
+        ));
+
+        // A server error response may carry an empty message.
+        let mut buf = BytesMut::from(&[2u8, 0][..]);
+        match new_outbound_codec().decode(&mut buf) {
+            Ok(Some(RPCCodedResponse::Error(code, message))) => {
+                assert_eq!(code, RPCResponseErrorCode::ServerError);
+                assert!(message.to_string().is_empty());
+            }
+            other => panic!("Expected an error response, got {:?}", other),
+        }
+        assert!(buf.is_empty());
+    }
 }
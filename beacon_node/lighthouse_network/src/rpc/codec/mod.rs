@@ -62,4 +62,65 @@ impl<T: EthSpec> Decoder for OutboundCodec<T> {
             OutboundCodec::SSZSnappy(codec) => codec.decode(src),
         }
     }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self {
+            OutboundCodec::SSZSnappy(codec) => codec.decode_eof(src),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::methods::RPCResponse;
+    use crate::rpc::protocol::{max_rpc_size, Encoding, Protocol, ProtocolId, Version};
+    use futures::StreamExt;
+    use std::sync::Arc;
+    use tokio_util::codec::FramedRead;
+    use types::{ForkContext, Hash256, MainnetEthSpec, Slot};
+
+    type Spec = MainnetEthSpec;
+
+    /// Frames a substream carrying the responses to a Status request, which ends after `bytes`.
+    fn status_response_stream(bytes: &[u8]) -> FramedRead<&[u8], OutboundCodec<Spec>> {
+        let fork_context = Arc::new(ForkContext::new::<Spec>(
+            Slot::new(0),
+            Hash256::zero(),
+            &Spec::default_spec(),
+        ));
+        let codec = OutboundCodec::SSZSnappy(BaseOutboundCodec::new(SSZSnappyOutboundCodec::new(
+            ProtocolId::new(Protocol::Status, Version::V1, Encoding::SSZSnappy),
+            max_rpc_size(&fork_context),
+            fork_context,
+        )));
+        FramedRead::new(bytes, codec)
+    }
+
+    /// Test that a substream closing after a complete chunk ends cleanly, while one closing part
+    /// way through a chunk is reported as incomplete.
+    #[tokio::test]
+    async fn test_outbound_stream_eof() {
+        let message = hex::decode("0054ff060000734e615070590032000006e71e7b54989925efd6c9cbcb8ceb9b5f71216f5137282bf6a1e3b50f64e42d6c7fb347abe07eb0db8200000005029e2800").unwrap();
+
+        // Nothing was sent.
+        assert!(status_response_stream(&[]).next().await.is_none());
+
+        // A complete chunk is returned, after which the stream ends cleanly.
+        let mut stream = status_response_stream(&message);
+        assert!(matches!(
+            stream.next().await,
+            Some(Ok(RPCCodedResponse::Success(RPCResponse::Status(_))))
+        ));
+        assert!(stream.next().await.is_none());
+
+        // The stream closes with a partial chunk buffered, both before and after the response
+        // code and length prefix have been consumed.
+        for split in [1, 2, 3, message.len() - 1] {
+            assert!(matches!(
+                status_response_stream(&message[..split]).next().await,
+                Some(Err(RPCError::IncompleteStream))
+            ));
+        }
+    }
 }
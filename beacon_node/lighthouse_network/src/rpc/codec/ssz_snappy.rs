@@ -390,7 +390,12 @@ fn handle_error<T>(
                 Ok(None)
             }
         }
-        _ => Err(err).map_err(RPCError::from),
+        // The snappy decoder only reads from the bytes already received, so any other error
+        // is a malformed message rather than a transport failure.
+        _ => Err(RPCError::InvalidData(format!(
+            "Failed to decompress snappy message: {}",
+            err
+        ))),
     }
 }
 
@@ -446,7 +451,10 @@ fn handle_length(
     } else {
         // Decode the length of the uncompressed bytes from an unsigned varint
         // Note: length-prefix of > 10 bytes(uint64) would be a decoding error
-        match uvi_codec.decode(bytes).map_err(RPCError::from)? {
+        match uvi_codec
+            .decode(bytes)
+            .map_err(|e| RPCError::InvalidData(format!("Invalid length-prefix: {}", e)))?
+        {
             Some(length) => {
                 *len = Some(length);
                 Ok(Some(length))
@@ -664,6 +672,8 @@ mod tests {
         ForkContext, FullPayload, Hash256, Signature, SignedBeaconBlock, Slot,
     };
 
+    use crate::rpc::codec::base::{BaseInboundCodec, BaseOutboundCodec};
+    use quickcheck_macros::quickcheck;
     use snap::write::FrameEncoder;
     use ssz::Encode;
     use std::io::Write;
@@ -1381,6 +1391,262 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_encode_then_decode_goodbye_reasons() {
+        let reasons = [
+            GoodbyeReason::ClientShutdown,
+            GoodbyeReason::IrrelevantNetwork,
+            GoodbyeReason::Fault,
+            GoodbyeReason::UnableToVerifyNetwork,
+            GoodbyeReason::TooManyPeers,
+            GoodbyeReason::BadScore,
+            GoodbyeReason::Banned,
+            GoodbyeReason::BannedIP,
+            GoodbyeReason::Unknown,
+        ];
+        for reason in reasons {
+            // The reason is sent over the wire as its `u64` code.
+            assert_eq!(
+                reason.as_ssz_bytes(),
+                u64::from(reason.clone()).as_ssz_bytes()
+            );
+            for fork_name in ForkName::list_all() {
+                encode_then_decode_request(OutboundRequest::Goodbye(reason.clone()), fork_name);
+            }
+        }
+    }
+
+    /// Test decoding a request that arrives one byte at a time. The length prefix of the request
+    /// spans two bytes, so it is also split across reads.
+    #[test]
+    fn test_decode_request_byte_by_byte() {
+        let fork_context = Arc::new(fork_context(ForkName::Base));
+        let max_packet_size = max_rpc_size(&fork_context);
+        let protocol = ProtocolId::new(Protocol::BlocksByRoot, Version::V2, Encoding::SSZSnappy);
+
+        // 10 block roots are 320 bytes, which needs a two byte length prefix.
+        let request = BlocksByRootRequest {
+            block_roots: VariableList::from(
+                (0..10u64).map(Hash256::from_low_u64_be).collect::<Vec<_>>(),
+            ),
+        };
+
+        let mut encoded = BytesMut::new();
+        SSZSnappyOutboundCodec::<Spec>::new(
+            protocol.clone(),
+            max_packet_size,
+            fork_context.clone(),
+        )
+        .encode(OutboundRequest::BlocksByRoot(request.clone()), &mut encoded)
+        .unwrap();
+        // The continuation bit is set on the first byte of the length prefix.
+        assert_ne!(encoded[0] & 0x80, 0);
+
+        let mut inbound_codec =
+            SSZSnappyInboundCodec::<Spec>::new(protocol, max_packet_size, fork_context);
+        let mut buf = BytesMut::new();
+        let (last_byte, first_bytes) = encoded.split_last().unwrap();
+
+        // Every partial read should wait for more bytes.
+        for byte in first_bytes {
+            buf.extend_from_slice(&[*byte]);
+            assert_eq!(inbound_codec.decode(&mut buf), Ok(None));
+        }
+
+        buf.extend_from_slice(&[*last_byte]);
+        assert_eq!(
+            inbound_codec.decode(&mut buf),
+            Ok(Some(InboundRequest::BlocksByRoot(request)))
+        );
+        assert!(buf.is_empty());
+    }
+
+    /// Test decoding a stream of two response chunks split in two at every byte boundary. Each
+    /// chunk must be decoded exactly once, whichever read completes it.
+    #[test]
+    fn test_decode_responses_split_at_every_byte() {
+        let fork_context = Arc::new(fork_context(ForkName::Altair));
+        let max_packet_size = max_rpc_size(&fork_context);
+        let protocol = ProtocolId::new(Protocol::BlocksByRange, Version::V2, Encoding::SSZSnappy);
+        let responses = vec![
+            RPCResponse::BlocksByRange(Arc::new(altair_block())),
+            RPCResponse::BlocksByRange(Arc::new(empty_base_block())),
+        ];
+
+        let mut encoded = BytesMut::new();
+        let mut inbound_codec = BaseInboundCodec::new(SSZSnappyInboundCodec::<Spec>::new(
+            protocol.clone(),
+            max_packet_size,
+            fork_context.clone(),
+        ));
+        for response in &responses {
+            inbound_codec
+                .encode(RPCCodedResponse::Success(response.clone()), &mut encoded)
+                .unwrap();
+        }
+
+        for split in 0..encoded.len() {
+            let mut outbound_codec = BaseOutboundCodec::new(SSZSnappyOutboundCodec::<Spec>::new(
+                protocol.clone(),
+                max_packet_size,
+                fork_context.clone(),
+            ));
+            let mut decoded = vec![];
+            let mut buf = BytesMut::from(&encoded[..split]);
+            while let Some(response) = outbound_codec.decode(&mut buf).unwrap() {
+                decoded.push(response);
+            }
+            buf.extend_from_slice(&encoded[split..]);
+            while let Some(response) = outbound_codec.decode(&mut buf).unwrap() {
+                decoded.push(response);
+            }
+
+            assert!(buf.is_empty(), "split at {}", split);
+            let decoded = decoded
+                .into_iter()
+                .map(|response| match response {
+                    RPCCodedResponse::Success(response) => response,
+                    other => panic!("Expected a successful response, got {:?}", other),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(decoded, responses, "split at {}", split);
+        }
+    }
+
+    /// Compresses the given ssz bytes into a request, without checking them against the protocol
+    /// limits.
+    fn encode_request_without_length_checks(bytes: &[u8]) -> BytesMut {
+        let mut dst = BytesMut::new();
+
+        let mut uvi_codec: Uvi<usize> = Uvi::default();
+        uvi_codec.encode(bytes.len(), &mut dst).unwrap();
+
+        let mut writer = FrameEncoder::new(Vec::new());
+        writer.write_all(bytes).unwrap();
+        writer.flush().unwrap();
+        dst.extend_from_slice(writer.get_ref());
+
+        dst
+    }
+
+    /// Attempts to decode the given bytes as an inbound request.
+    fn decode_request(
+        protocol: Protocol,
+        version: Version,
+        message: &mut BytesMut,
+        fork_name: ForkName,
+    ) -> Result<Option<InboundRequest<Spec>>, RPCError> {
+        let snappy_protocol_id = ProtocolId::new(protocol, version, Encoding::SSZSnappy);
+        let fork_context = Arc::new(fork_context(fork_name));
+        let max_packet_size = max_rpc_size(&fork_context);
+        let mut snappy_inbound_codec =
+            SSZSnappyInboundCodec::<Spec>::new(snappy_protocol_id, max_packet_size, fork_context);
+        snappy_inbound_codec.decode(message)
+    }
+
+    #[test]
+    fn test_decode_blocks_by_root_request_limits() {
+        for version in [Version::V1, Version::V2] {
+            // A request for a few roots is decoded.
+            let block_roots = (0..3u64).map(Hash256::from_low_u64_be).collect::<Vec<_>>();
+            let mut encoded = encode_request_without_length_checks(&block_roots.as_ssz_bytes());
+            assert_eq!(
+                decode_request(
+                    Protocol::BlocksByRoot,
+                    version.clone(),
+                    &mut encoded,
+                    ForkName::Base
+                ),
+                Ok(Some(InboundRequest::BlocksByRoot(BlocksByRootRequest {
+                    block_roots: VariableList::from(block_roots),
+                })))
+            );
+
+            // A request without any roots is within the ssz bounds, it is rejected by the worker.
+            let mut encoded =
+                encode_request_without_length_checks(&Vec::<Hash256>::new().as_ssz_bytes());
+            assert_eq!(
+                decode_request(
+                    Protocol::BlocksByRoot,
+                    version.clone(),
+                    &mut encoded,
+                    ForkName::Base
+                ),
+                Ok(Some(InboundRequest::BlocksByRoot(BlocksByRootRequest {
+                    block_roots: VariableList::empty(),
+                })))
+            );
+
+            // A request for more than `MAX_REQUEST_BLOCKS` roots is invalid.
+            let block_roots = vec![Hash256::zero(); MAX_REQUEST_BLOCKS as usize + 1];
+            let mut encoded = encode_request_without_length_checks(&block_roots.as_ssz_bytes());
+            assert!(matches!(
+                decode_request(
+                    Protocol::BlocksByRoot,
+                    version.clone(),
+                    &mut encoded,
+                    ForkName::Base
+                )
+                .unwrap_err(),
+                RPCError::InvalidData(_)
+            ));
+        }
+    }
+
+    /// Test that request lengths exactly at the limits are accepted, and that a length one byte
+    /// over the limit is rejected before the payload is read.
+    #[test]
+    fn test_decode_request_length_boundaries() {
+        // A request for exactly `MAX_REQUEST_BLOCKS` roots is decoded.
+        let block_roots = vec![Hash256::zero(); MAX_REQUEST_BLOCKS as usize];
+        let mut encoded = encode_request_without_length_checks(&block_roots.as_ssz_bytes());
+        assert_eq!(
+            decode_request(
+                Protocol::BlocksByRoot,
+                Version::V2,
+                &mut encoded,
+                ForkName::Base
+            ),
+            Ok(Some(InboundRequest::BlocksByRoot(BlocksByRootRequest {
+                block_roots: VariableList::from(block_roots),
+            })))
+        );
+
+        // A status message of exactly its fixed length is decoded.
+        let mut encoded = encode_request_without_length_checks(&status_message().as_ssz_bytes());
+        assert_eq!(
+            decode_request(Protocol::Status, Version::V1, &mut encoded, ForkName::Base),
+            Ok(Some(InboundRequest::Status(status_message())))
+        );
+
+        // A status message one byte over its fixed length is rejected.
+        let mut status_bytes = status_message().as_ssz_bytes();
+        status_bytes.push(0);
+        let mut encoded = encode_request_without_length_checks(&status_bytes);
+        assert!(matches!(
+            decode_request(Protocol::Status, Version::V1, &mut encoded, ForkName::Base)
+                .unwrap_err(),
+            RPCError::InvalidData(_)
+        ));
+
+        // A length prefix one byte over the `BlocksByRoot` limit is rejected from the prefix
+        // alone, without waiting for the payload.
+        let mut encoded = BytesMut::new();
+        Uvi::<usize>::default()
+            .encode(*BLOCKS_BY_ROOT_REQUEST_MAX + 1, &mut encoded)
+            .unwrap();
+        assert!(matches!(
+            decode_request(
+                Protocol::BlocksByRoot,
+                Version::V2,
+                &mut encoded,
+                ForkName::Base
+            )
+            .unwrap_err(),
+            RPCError::InvalidData(_)
+        ));
+    }
+
     /// Test a malicious snappy encoding for a V1 `Status` message where the attacker
     /// sends a valid message filled with a stream of useless padding before the actual message.
     #[test]
@@ -1538,4 +1804,259 @@ mod tests {
             RPCError::InvalidData(_)
         ));
     }
+
+    /// Every protocol id peers can negotiate with us.
+    fn negotiable_protocol_ids() -> Vec<ProtocolId> {
+        [
+            (Protocol::Status, Version::V1),
+            (Protocol::Goodbye, Version::V1),
+            (Protocol::BlocksByRange, Version::V1),
+            (Protocol::BlocksByRange, Version::V2),
+            (Protocol::BlocksByRoot, Version::V1),
+            (Protocol::BlocksByRoot, Version::V2),
+            (Protocol::Ping, Version::V1),
+            (Protocol::MetaData, Version::V1),
+            (Protocol::MetaData, Version::V2),
+            (Protocol::LightClientBootstrap, Version::V1),
+        ]
+        .into_iter()
+        .map(|(protocol, version)| ProtocolId::new(protocol, version, Encoding::SSZSnappy))
+        .collect()
+    }
+
+    /// Picks a protocol id and fork from arbitrary indices.
+    fn pick_protocol_and_fork(protocol_index: u8, fork_index: u8) -> (ProtocolId, ForkName) {
+        let protocol_ids = negotiable_protocol_ids();
+        let protocol_id = protocol_ids[protocol_index as usize % protocol_ids.len()].clone();
+        let forks = ForkName::list_all();
+        (protocol_id, forks[fork_index as usize % forks.len()])
+    }
+
+    /// Decodes `bytes` with `codec` until it errors or needs more bytes. Every decoded item must
+    /// be within `max_packet_size` and the SSZ limits returned along with its length by
+    /// `ssz_len_and_limits`, so a peer can't make the decoder allocate more than the length bounds
+    /// allow.
+    fn decode_all<D: Decoder>(
+        mut codec: D,
+        bytes: &[u8],
+        max_packet_size: usize,
+        ssz_len_and_limits: impl Fn(&D::Item) -> (usize, RpcLimits),
+    ) {
+        let mut buf = BytesMut::from(bytes);
+        let mut remaining = buf.len();
+        while let Ok(Some(item)) = codec.decode(&mut buf) {
+            let (ssz_len, limits) = ssz_len_and_limits(&item);
+            assert!(
+                !limits.is_out_of_bounds(ssz_len, max_packet_size),
+                "decoded item of length {} is out of bounds {:?}",
+                ssz_len,
+                limits
+            );
+            // Stop if the codec made no progress
+            if buf.is_empty() || buf.len() == remaining {
+                break;
+            }
+            remaining = buf.len();
+        }
+    }
+
+    /// Decodes `bytes` as inbound requests, see `decode_all`.
+    fn decode_all_requests(protocol_id: ProtocolId, bytes: &[u8], fork_name: ForkName) {
+        let fork_context = Arc::new(fork_context(fork_name));
+        let max_packet_size = max_rpc_size(&fork_context);
+        let codec =
+            SSZSnappyInboundCodec::<Spec>::new(protocol_id.clone(), max_packet_size, fork_context);
+        decode_all(codec, bytes, max_packet_size, |request| {
+            let ssz_len = match request {
+                InboundRequest::Status(req) => req.ssz_bytes_len(),
+                InboundRequest::Goodbye(req) => req.ssz_bytes_len(),
+                InboundRequest::BlocksByRange(req) => req.ssz_bytes_len(),
+                InboundRequest::BlocksByRoot(req) => req.block_roots.ssz_bytes_len(),
+                InboundRequest::LightClientBootstrap(req) => req.ssz_bytes_len(),
+                InboundRequest::Ping(req) => req.ssz_bytes_len(),
+                InboundRequest::MetaData(_) => 0,
+            };
+            (ssz_len, protocol_id.rpc_request_limits())
+        });
+    }
+
+    /// Decodes `bytes` as response chunks, including their response code, see `decode_all`.
+    fn decode_all_responses(protocol_id: ProtocolId, bytes: &[u8], fork_name: ForkName) {
+        let fork_context = Arc::new(fork_context(fork_name));
+        let max_packet_size = max_rpc_size(&fork_context);
+        let codec = BaseOutboundCodec::new(SSZSnappyOutboundCodec::<Spec>::new(
+            protocol_id.clone(),
+            max_packet_size,
+            fork_context.clone(),
+        ));
+        decode_all(codec, bytes, max_packet_size, |response| match response {
+            RPCCodedResponse::Success(resp) => {
+                let ssz_len = match resp {
+                    RPCResponse::Status(res) => res.ssz_bytes_len(),
+                    RPCResponse::BlocksByRange(res) => res.ssz_bytes_len(),
+                    RPCResponse::BlocksByRoot(res) => res.ssz_bytes_len(),
+                    RPCResponse::LightClientBootstrap(res) => res.ssz_bytes_len(),
+                    RPCResponse::Pong(res) => res.data.ssz_bytes_len(),
+                    RPCResponse::MetaData(res) => res.ssz_bytes_len(),
+                };
+                (
+                    ssz_len,
+                    protocol_id.rpc_response_limits::<Spec>(&fork_context),
+                )
+            }
+            RPCCodedResponse::Error(_, err) => (
+                err.ssz_bytes_len(),
+                RpcLimits::new(*ERROR_TYPE_MIN, *ERROR_TYPE_MAX),
+            ),
+            RPCCodedResponse::StreamTermination(_) => (0, RpcLimits::new(0, 0)),
+        });
+    }
+
+    /// Returns a length prefix just over both the SSZ limits and `max_packet_size`, followed by
+    /// `bytes`.
+    fn over_limit_length_prefix(
+        limits: RpcLimits,
+        max_packet_size: usize,
+        bytes: &[u8],
+    ) -> Vec<u8> {
+        let length = std::cmp::min(limits.max, max_packet_size).saturating_add(1);
+        let mut buf = BytesMut::new();
+        Uvi::<usize>::default().encode(length, &mut buf).unwrap();
+        buf.extend_from_slice(bytes);
+        buf.to_vec()
+    }
+
+    /// Test that arbitrary bytes from a peer never panic the request decoder.
+    #[quickcheck]
+    fn quickcheck_decode_arbitrary_request(protocol_index: u8, fork_index: u8, bytes: Vec<u8>) {
+        let (protocol_id, fork_name) = pick_protocol_and_fork(protocol_index, fork_index);
+        decode_all_requests(protocol_id, &bytes, fork_name);
+    }
+
+    /// Test that arbitrary bytes from a peer never panic the response decoder.
+    #[quickcheck]
+    fn quickcheck_decode_arbitrary_response(protocol_index: u8, fork_index: u8, bytes: Vec<u8>) {
+        let (protocol_id, fork_name) = pick_protocol_and_fork(protocol_index, fork_index);
+        decode_all_responses(protocol_id, &bytes, fork_name);
+    }
+
+    /// Test that corrupting a byte of a valid request never panics the request decoder.
+    #[quickcheck]
+    fn quickcheck_decode_corrupted_request(index: usize, byte: u8) {
+        let protocol_id = ProtocolId::new(Protocol::Status, Version::V1, Encoding::SSZSnappy);
+        let fork_context = Arc::new(fork_context(ForkName::Base));
+        let max_packet_size = max_rpc_size(&fork_context);
+        let mut buf = BytesMut::new();
+        SSZSnappyOutboundCodec::<Spec>::new(protocol_id.clone(), max_packet_size, fork_context)
+            .encode(OutboundRequest::Status(status_message()), &mut buf)
+            .unwrap();
+
+        let index = index % buf.len();
+        buf[index] = byte;
+        decode_all_requests(protocol_id, &buf, ForkName::Base);
+    }
+
+    /// Test that corrupting a byte of a valid response never panics the response decoder.
+    #[quickcheck]
+    fn quickcheck_decode_corrupted_response(index: usize, byte: u8) {
+        let protocol_id =
+            ProtocolId::new(Protocol::BlocksByRange, Version::V2, Encoding::SSZSnappy);
+        let fork_context = Arc::new(fork_context(ForkName::Altair));
+        let max_packet_size = max_rpc_size(&fork_context);
+        let mut buf = BytesMut::new();
+        BaseInboundCodec::new(SSZSnappyInboundCodec::<Spec>::new(
+            protocol_id.clone(),
+            max_packet_size,
+            fork_context,
+        ))
+        .encode(
+            RPCCodedResponse::Success(RPCResponse::BlocksByRange(Arc::new(altair_block()))),
+            &mut buf,
+        )
+        .unwrap();
+
+        let index = index % buf.len();
+        buf[index] = byte;
+        decode_all_responses(protocol_id, &buf, ForkName::Altair);
+    }
+
+    /// Test that a request whose length prefix is over the protocol's limits is rejected, whatever
+    /// follows it.
+    #[quickcheck]
+    fn quickcheck_decode_over_limit_request(protocol_index: u8, fork_index: u8, bytes: Vec<u8>) {
+        let (protocol_id, fork_name) = pick_protocol_and_fork(protocol_index, fork_index);
+        // MetaData requests carry no payload, so there is no length to check.
+        if protocol_id.message_name == Protocol::MetaData {
+            return;
+        }
+        let fork_context = Arc::new(fork_context(fork_name));
+        let max_packet_size = max_rpc_size(&fork_context);
+        let mut buf = BytesMut::from(
+            over_limit_length_prefix(protocol_id.rpc_request_limits(), max_packet_size, &bytes)
+                .as_slice(),
+        );
+        let mut codec =
+            SSZSnappyInboundCodec::<Spec>::new(protocol_id, max_packet_size, fork_context);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(RPCError::InvalidData(_))
+        ));
+    }
+
+    /// Test that a response chunk whose length prefix is over the protocol's limits is rejected,
+    /// whatever follows it.
+    #[quickcheck]
+    fn quickcheck_decode_over_limit_response(protocol_index: u8, fork_index: u8, bytes: Vec<u8>) {
+        let (protocol_id, fork_name) = pick_protocol_and_fork(protocol_index, fork_index);
+        let fork_context = Arc::new(fork_context(fork_name));
+        let max_packet_size = max_rpc_size(&fork_context);
+        let limits = protocol_id.rpc_response_limits::<Spec>(&fork_context);
+
+        // A success response code, followed by the context bytes if the protocol has them.
+        let mut chunk = vec![0];
+        if protocol_id.has_context_bytes() {
+            chunk.extend_from_slice(&fork_context.to_context_bytes(fork_name).unwrap());
+        }
+        chunk.extend(over_limit_length_prefix(limits, max_packet_size, &bytes));
+
+        let mut codec = BaseOutboundCodec::new(SSZSnappyOutboundCodec::<Spec>::new(
+            protocol_id,
+            max_packet_size,
+            fork_context,
+        ));
+        assert!(matches!(
+            codec.decode(&mut BytesMut::from(chunk.as_slice())),
+            Err(RPCError::InvalidData(_))
+        ));
+    }
+
+    /// Test that the largest possible length prefixes are rejected for every protocol.
+    #[test]
+    fn test_decode_maximal_length_prefixes() {
+        let mut max_length_prefix = BytesMut::new();
+        Uvi::<usize>::default()
+            .encode(usize::MAX, &mut max_length_prefix)
+            .unwrap();
+        // A varint longer than any `usize`
+        let overlong_prefix = vec![0xff; 16];
+
+        for protocol_id in negotiable_protocol_ids() {
+            for fork_name in ForkName::list_all() {
+                for bytes in [max_length_prefix.to_vec(), overlong_prefix.clone()] {
+                    let fork_context = Arc::new(fork_context(fork_name));
+                    let max_packet_size = max_rpc_size(&fork_context);
+                    let mut codec = SSZSnappyInboundCodec::<Spec>::new(
+                        protocol_id.clone(),
+                        max_packet_size,
+                        fork_context,
+                    );
+                    let mut buf = BytesMut::from(bytes.as_slice());
+                    // MetaData requests carry no payload, so there is no length to check.
+                    if protocol_id.message_name != Protocol::MetaData {
+                        assert!(codec.decode(&mut buf).is_err());
+                    }
+                }
+            }
+        }
+    }
 }
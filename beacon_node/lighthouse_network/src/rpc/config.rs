@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     fmt::{Debug, Display},
     str::FromStr,
     time::Duration,
@@ -58,6 +59,28 @@ impl FromStr for ProtocolQuota {
     }
 }
 
+/// Parses a ';' separated list of [`ProtocolQuota`]. Protocols specified more than once use only
+/// the first given Quota.
+fn parse_protocol_quotas(s: &str) -> Result<HashMap<Protocol, Quota>, &'static str> {
+    let mut quotas = HashMap::new();
+    for proto_def in s.split(';') {
+        let ProtocolQuota { protocol, quota } = proto_def.parse()?;
+        quotas.entry(protocol).or_insert(quota);
+    }
+    Ok(quotas)
+}
+
+/// Formats a quota as `tokens/time_in_seconds` for the configs' `Debug` impls.
+macro_rules! fmt_q {
+    ($quota:expr) => {
+        &format_args!(
+            "{}/{}s",
+            $quota.max_tokens,
+            $quota.replenish_all_every.as_secs()
+        )
+    };
+}
+
 /// Configurations for the rate limiter applied to outbound requests (made by the node itself).
 #[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct OutboundRateLimiterConfig {
@@ -94,16 +117,6 @@ impl Default for OutboundRateLimiterConfig {
 
 impl Debug for OutboundRateLimiterConfig {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        macro_rules! fmt_q {
-            ($quota:expr) => {
-                &format_args!(
-                    "{}/{}s",
-                    $quota.max_tokens,
-                    $quota.replenish_all_every.as_secs()
-                )
-            };
-        }
-
         f.debug_struct("OutboundRateLimiterConfig")
             .field("ping", fmt_q!(&self.ping_quota))
             .field("metadata", fmt_q!(&self.meta_data_quota))
@@ -123,34 +136,113 @@ impl FromStr for OutboundRateLimiterConfig {
     type Err = &'static str;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut ping_quota = None;
-        let mut meta_data_quota = None;
-        let mut status_quota = None;
-        let mut goodbye_quota = None;
-        let mut blocks_by_range_quota = None;
-        let mut blocks_by_root_quota = None;
-        for proto_def in s.split(';') {
-            let ProtocolQuota { protocol, quota } = proto_def.parse()?;
-            let quota = Some(quota);
-            match protocol {
-                Protocol::Status => status_quota = status_quota.or(quota),
-                Protocol::Goodbye => goodbye_quota = goodbye_quota.or(quota),
-                Protocol::BlocksByRange => blocks_by_range_quota = blocks_by_range_quota.or(quota),
-                Protocol::BlocksByRoot => blocks_by_root_quota = blocks_by_root_quota.or(quota),
-                Protocol::Ping => ping_quota = ping_quota.or(quota),
-                Protocol::MetaData => meta_data_quota = meta_data_quota.or(quota),
-                Protocol::LightClientBootstrap => return Err("Lighthouse does not send LightClientBootstrap requests. Quota should not be set."),
-            }
+        let mut quotas = parse_protocol_quotas(s)?;
+        if quotas.contains_key(&Protocol::LightClientBootstrap) {
+            return Err(
+                "Lighthouse does not send LightClientBootstrap requests. Quota should not be set.",
+            );
         }
+        let mut quota_or =
+            |protocol: Protocol, default: Quota| quotas.remove(&protocol).unwrap_or(default);
         Ok(OutboundRateLimiterConfig {
-            ping_quota: ping_quota.unwrap_or(Self::DEFAULT_PING_QUOTA),
-            meta_data_quota: meta_data_quota.unwrap_or(Self::DEFAULT_META_DATA_QUOTA),
-            status_quota: status_quota.unwrap_or(Self::DEFAULT_STATUS_QUOTA),
-            goodbye_quota: goodbye_quota.unwrap_or(Self::DEFAULT_GOODBYE_QUOTA),
-            blocks_by_range_quota: blocks_by_range_quota
-                .unwrap_or(Self::DEFAULT_BLOCKS_BY_RANGE_QUOTA),
-            blocks_by_root_quota: blocks_by_root_quota
-                .unwrap_or(Self::DEFAULT_BLOCKS_BY_ROOT_QUOTA),
+            ping_quota: quota_or(Protocol::Ping, Self::DEFAULT_PING_QUOTA),
+            meta_data_quota: quota_or(Protocol::MetaData, Self::DEFAULT_META_DATA_QUOTA),
+            status_quota: quota_or(Protocol::Status, Self::DEFAULT_STATUS_QUOTA),
+            goodbye_quota: quota_or(Protocol::Goodbye, Self::DEFAULT_GOODBYE_QUOTA),
+            blocks_by_range_quota: quota_or(
+                Protocol::BlocksByRange,
+                Self::DEFAULT_BLOCKS_BY_RANGE_QUOTA,
+            ),
+            blocks_by_root_quota: quota_or(
+                Protocol::BlocksByRoot,
+                Self::DEFAULT_BLOCKS_BY_ROOT_QUOTA,
+            ),
+        })
+    }
+}
+
+/// Configurations for the rate limiter applied to inbound requests (made by our peers).
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InboundRateLimiterConfig {
+    pub(super) ping_quota: Quota,
+    pub(super) meta_data_quota: Quota,
+    pub(super) status_quota: Quota,
+    pub(super) goodbye_quota: Quota,
+    pub(super) blocks_by_range_quota: Quota,
+    pub(super) blocks_by_root_quota: Quota,
+    pub(super) light_client_bootstrap_quota: Quota,
+}
+
+impl InboundRateLimiterConfig {
+    pub const DEFAULT_PING_QUOTA: Quota = Quota::n_every(2, 10);
+    pub const DEFAULT_META_DATA_QUOTA: Quota = Quota::n_every(2, 5);
+    pub const DEFAULT_STATUS_QUOTA: Quota = Quota::n_every(5, 15);
+    pub const DEFAULT_GOODBYE_QUOTA: Quota = Quota::one_every(10);
+    pub const DEFAULT_BLOCKS_BY_RANGE_QUOTA: Quota =
+        Quota::n_every(methods::MAX_REQUEST_BLOCKS, 10);
+    pub const DEFAULT_BLOCKS_BY_ROOT_QUOTA: Quota = Quota::n_every(128, 10);
+    pub const DEFAULT_LIGHT_CLIENT_BOOTSTRAP_QUOTA: Quota = Quota::one_every(10);
+}
+
+impl Default for InboundRateLimiterConfig {
+    fn default() -> Self {
+        InboundRateLimiterConfig {
+            ping_quota: Self::DEFAULT_PING_QUOTA,
+            meta_data_quota: Self::DEFAULT_META_DATA_QUOTA,
+            status_quota: Self::DEFAULT_STATUS_QUOTA,
+            goodbye_quota: Self::DEFAULT_GOODBYE_QUOTA,
+            blocks_by_range_quota: Self::DEFAULT_BLOCKS_BY_RANGE_QUOTA,
+            blocks_by_root_quota: Self::DEFAULT_BLOCKS_BY_ROOT_QUOTA,
+            light_client_bootstrap_quota: Self::DEFAULT_LIGHT_CLIENT_BOOTSTRAP_QUOTA,
+        }
+    }
+}
+
+impl Debug for InboundRateLimiterConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InboundRateLimiterConfig")
+            .field("ping", fmt_q!(&self.ping_quota))
+            .field("metadata", fmt_q!(&self.meta_data_quota))
+            .field("status", fmt_q!(&self.status_quota))
+            .field("goodbye", fmt_q!(&self.goodbye_quota))
+            .field("blocks_by_range", fmt_q!(&self.blocks_by_range_quota))
+            .field("blocks_by_root", fmt_q!(&self.blocks_by_root_quota))
+            .field(
+                "light_client_bootstrap",
+                fmt_q!(&self.light_client_bootstrap_quota),
+            )
+            .finish()
+    }
+}
+
+/// Parse configurations for the inbound rate limiter. Protocols that are not specified use
+/// the default values. Protocol specified more than once use only the first given Quota.
+///
+/// The expected format is a ';' separated list of [`ProtocolQuota`].
+impl FromStr for InboundRateLimiterConfig {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut quotas = parse_protocol_quotas(s)?;
+        let mut quota_or =
+            |protocol: Protocol, default: Quota| quotas.remove(&protocol).unwrap_or(default);
+        Ok(InboundRateLimiterConfig {
+            ping_quota: quota_or(Protocol::Ping, Self::DEFAULT_PING_QUOTA),
+            meta_data_quota: quota_or(Protocol::MetaData, Self::DEFAULT_META_DATA_QUOTA),
+            status_quota: quota_or(Protocol::Status, Self::DEFAULT_STATUS_QUOTA),
+            goodbye_quota: quota_or(Protocol::Goodbye, Self::DEFAULT_GOODBYE_QUOTA),
+            blocks_by_range_quota: quota_or(
+                Protocol::BlocksByRange,
+                Self::DEFAULT_BLOCKS_BY_RANGE_QUOTA,
+            ),
+            blocks_by_root_quota: quota_or(
+                Protocol::BlocksByRoot,
+                Self::DEFAULT_BLOCKS_BY_ROOT_QUOTA,
+            ),
+            light_client_bootstrap_quota: quota_or(
+                Protocol::LightClientBootstrap,
+                Self::DEFAULT_LIGHT_CLIENT_BOOTSTRAP_QUOTA,
+            ),
         })
     }
 }
@@ -170,4 +262,25 @@ mod tests {
         };
         assert_eq!(quota.to_string().parse(), Ok(quota))
     }
+
+    #[test]
+    fn test_parse_inbound_rate_limiter_config() {
+        let config: InboundRateLimiterConfig = "beacon_blocks_by_range:128/10;ping:1/5;ping:3/5"
+            .parse()
+            .unwrap();
+        assert_eq!(config.blocks_by_range_quota, Quota::n_every(128, 10));
+        // Only the first quota given for a protocol is used.
+        assert_eq!(config.ping_quota, Quota::n_every(1, 5));
+        // Protocols that are not specified use the defaults.
+        assert_eq!(
+            config.status_quota,
+            InboundRateLimiterConfig::DEFAULT_STATUS_QUOTA
+        );
+        assert_eq!(
+            config.light_client_bootstrap_quota,
+            InboundRateLimiterConfig::DEFAULT_LIGHT_CLIENT_BOOTSTRAP_QUOTA
+        );
+
+        assert!("ping:1".parse::<InboundRateLimiterConfig>().is_err());
+    }
 }
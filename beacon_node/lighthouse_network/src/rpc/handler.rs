@@ -5,6 +5,7 @@ use super::methods::{GoodbyeReason, RPCCodedResponse, RPCResponseErrorCode, Resp
 use super::outbound::OutboundRequestContainer;
 use super::protocol::{max_rpc_size, InboundRequest, Protocol, RPCError, RPCProtocol};
 use super::{RPCReceived, RPCSend, ReqId};
+use crate::metrics;
 use crate::rpc::outbound::{OutboundFramed, OutboundRequest};
 use crate::rpc::protocol::InboundFramed;
 use fnv::FnvHashMap;
@@ -34,6 +35,9 @@ use types::{EthSpec, ForkContext};
 /// The time (in seconds) before a substream that is awaiting a response from the user times out.
 pub const RESPONSE_TIMEOUT: u64 = 10;
 
+/// The time (in seconds) after which the first response chunk to a request is logged as slow.
+pub const SLOW_RESPONSE_THRESHOLD: u64 = 3;
+
 /// The number of times to retry an outbound upgrade in the case of IO errors.
 const IO_ERROR_RETRIES: u8 = 3;
 
@@ -47,6 +51,13 @@ const MAX_INBOUND_SUBSTREAMS: usize = 32;
 #[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 pub struct SubstreamId(usize);
 
+#[cfg(test)]
+impl SubstreamId {
+    pub(crate) fn new(id: usize) -> Self {
+        Self(id)
+    }
+}
+
 type InboundSubstream<TSpec> = InboundFramed<NegotiatedSubstream, TSpec>;
 
 /// Events the handler emits to the behaviour.
@@ -89,12 +100,16 @@ where
     /// Queue of events to produce in `poll()`.
     events_out: SmallVec<[HandlerEvent<Id, TSpec>; 4]>,
 
-    /// Queue of outbound substreams to open.
-    dial_queue: SmallVec<[(Id, OutboundRequest<TSpec>); 4]>,
+    /// Queue of outbound substreams to open, along with the time each request was queued.
+    dial_queue: SmallVec<[(Id, OutboundRequest<TSpec>, Instant); 4]>,
 
     /// Current number of concurrent outbound substreams being opened.
     dial_negotiated: u32,
 
+    /// Requests cancelled while their outbound substream was being opened. Their substreams are
+    /// dropped as soon as they are negotiated.
+    cancelled_requests: SmallVec<[Id; 4]>,
+
     /// Current inbound substreams awaiting processing.
     inbound_substreams: FnvHashMap<SubstreamId, InboundInfo<TSpec>>,
 
@@ -126,6 +141,12 @@ where
     /// Fork specific info.
     fork_context: Arc<ForkContext>,
 
+    /// Timeout for a substream awaiting a response chunk.
+    resp_timeout: Duration,
+
+    /// Time after which the first response chunk to a request is logged as slow.
+    slow_response_threshold: Duration,
+
     /// Waker, to be sure the handler gets polled when needed.
     waker: Option<std::task::Waker>,
 
@@ -175,6 +196,9 @@ struct OutboundInfo<Id, TSpec: EthSpec> {
     remaining_chunks: Option<u64>,
     /// `Id` as given by the application that sent the request.
     req_id: Id,
+    /// When the request was queued, so that dialing and negotiation are included in the response
+    /// time. Cleared once the first response chunk is received.
+    sent_at: Option<Instant>,
 }
 
 /// State of an inbound substream connection.
@@ -208,17 +232,21 @@ pub enum OutboundSubstreamState<TSpec: EthSpec> {
 impl<Id, TSpec> RPCHandler<Id, TSpec>
 where
     TSpec: EthSpec,
+    Id: ReqId,
 {
     pub fn new(
         listen_protocol: SubstreamProtocol<RPCProtocol<TSpec>, ()>,
         fork_context: Arc<ForkContext>,
         log: &slog::Logger,
+        resp_timeout: Duration,
+        slow_response_threshold: Duration,
     ) -> Self {
         RPCHandler {
             listen_protocol,
             events_out: SmallVec::new(),
             dial_queue: SmallVec::new(),
             dial_negotiated: 0,
+            cancelled_requests: SmallVec::new(),
             inbound_substreams: FnvHashMap::default(),
             outbound_substreams: FnvHashMap::default(),
             inbound_substreams_delay: DelayQueue::new(),
@@ -231,6 +259,8 @@ where
             fork_context,
             waker: None,
             log: log.clone(),
+            resp_timeout,
+            slow_response_threshold,
         }
     }
 
@@ -242,7 +272,7 @@ where
                 debug!(self.log, "Starting handler shutdown"; "unsent_queued_requests" => self.dial_queue.len());
             }
             // We now drive to completion communications already dialed/established
-            while let Some((id, req)) = self.dial_queue.pop() {
+            while let Some((id, req, _)) = self.dial_queue.pop() {
                 self.events_out.push(Err(HandlerErr::Outbound {
                     error: RPCError::Disconnected,
                     proto: req.protocol(),
@@ -252,7 +282,8 @@ where
 
             // Queue our goodbye message.
             if let Some((id, reason)) = goodbye_reason {
-                self.dial_queue.push((id, OutboundRequest::Goodbye(reason)));
+                self.dial_queue
+                    .push((id, OutboundRequest::Goodbye(reason), Instant::now()));
             }
 
             self.state = HandlerState::ShuttingDown(Box::pin(sleep_until(
@@ -261,11 +292,12 @@ where
         }
     }
 
-    /// Opens an outbound substream with a request.
-    fn send_request(&mut self, id: Id, req: OutboundRequest<TSpec>) {
+    /// Opens an outbound substream with a request. `queued_at` is the time the request was first
+    /// queued, from which its response time is measured.
+    fn send_request(&mut self, id: Id, req: OutboundRequest<TSpec>, queued_at: Instant) {
         match self.state {
             HandlerState::Active => {
-                self.dial_queue.push((id, req));
+                self.dial_queue.push((id, req, queued_at));
             }
             _ => self.events_out.push(Err(HandlerErr::Outbound {
                 error: RPCError::Disconnected,
@@ -275,6 +307,70 @@ where
         }
     }
 
+    /// Stops handling the request with the given id. A request waiting to be sent is dropped. The
+    /// substream of a request in flight is dropped and no further responses are reported for it.
+    fn cancel_request(&mut self, id: Id) {
+        let queued = self.dial_queue.len();
+        self.dial_queue.retain(|(req_id, _, _)| *req_id != id);
+        if self.dial_queue.len() != queued {
+            return;
+        }
+
+        let cancelled_substream = self
+            .outbound_substreams
+            .iter()
+            .find(|(_, info)| info.req_id == id)
+            .map(|(outbound_id, _)| *outbound_id);
+        if let Some(info) = cancelled_substream
+            .and_then(|outbound_id| self.outbound_substreams.remove(&outbound_id))
+        {
+            self.outbound_substreams_delay.remove(&info.delay_key);
+            debug!(self.log, "Outbound request cancelled"; "protocol" => %info.proto, "id" => ?id);
+        } else if self.dial_negotiated > 0 {
+            // The request may be waiting for its substream to be negotiated.
+            self.cancelled_requests.push(id);
+        }
+    }
+
+    /// Returns true if the request was cancelled while its outbound substream was being opened.
+    /// Must be called once the substream is no longer being opened.
+    fn take_cancelled(&mut self, id: &Id) -> bool {
+        let position = self
+            .cancelled_requests
+            .iter()
+            .position(|req_id| req_id == id);
+        if let Some(position) = position {
+            self.cancelled_requests.swap_remove(position);
+        }
+        // Cancelled ids of requests that had already completed are never taken.
+        if self.dial_negotiated == 0 {
+            self.cancelled_requests.clear();
+        }
+        position.is_some()
+    }
+
+    /// Decides whether the newly negotiated outbound substream of request `id` is kept. The
+    /// substream of a cancelled request is dropped silently. If the handler is deactivated the
+    /// substream is dropped and the request fails with `RPCError::Disconnected`, so it is never
+    /// both reported as failed and answered.
+    fn accept_negotiated_outbound(&mut self, id: Id, proto: Protocol) -> bool {
+        if self.take_cancelled(&id) {
+            debug!(self.log, "Dropping substream of cancelled request"; "protocol" => %proto, "id" => ?id);
+            return false;
+        }
+
+        // accept outbound connections only if the handler is not deactivated
+        if matches!(self.state, HandlerState::Deactivated) {
+            self.events_out.push(Err(HandlerErr::Outbound {
+                error: RPCError::Disconnected,
+                proto,
+                id,
+            }));
+            return false;
+        }
+        true
+    }
+
     /// Sends a response to a peer's request.
     // NOTE: If the substream has closed due to inactivity, or the substream is in the
     // wrong state a response will fail silently.
@@ -308,6 +404,22 @@ where
         }
         inbound_info.pending_items.push_back(response);
     }
+
+    /// Records the time taken by the peer to send the first response chunk of a request and logs
+    /// it if it exceeded the slow response threshold. Returns whether the response was slow.
+    ///
+    /// The histogram is the main signal. Slow responses are common during range sync, so they
+    /// are only logged at debug level.
+    fn observe_response_time(&self, proto: Protocol, id: &Id, sent_at: Instant) -> bool {
+        let elapsed = sent_at.elapsed();
+        metrics::observe_timer_vec(&metrics::RPC_RESPONSE_TIME, &[proto.as_ref()], elapsed);
+        let slow = elapsed > self.slow_response_threshold;
+        if slow {
+            debug!(self.log, "Slow RPC response"; "protocol" => %proto, "id" => ?id,
+                "elapsed_ms" => elapsed.as_millis());
+        }
+        slow
+    }
 }
 
 impl<Id, TSpec> ConnectionHandler for RPCHandler<Id, TSpec>
@@ -320,7 +432,7 @@ where
     type Error = RPCError;
     type InboundProtocol = RPCProtocol<TSpec>;
     type OutboundProtocol = OutboundRequestContainer<TSpec>;
-    type OutboundOpenInfo = (Id, OutboundRequest<TSpec>); // Keep track of the id and the request
+    type OutboundOpenInfo = (Id, OutboundRequest<TSpec>, Instant); // Keep track of the id, the request and its queue time
     type InboundOpenInfo = ();
 
     fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol, ()> {
@@ -333,26 +445,22 @@ where
         request_info: Self::OutboundOpenInfo,
     ) {
         self.dial_negotiated -= 1;
-        let (id, request) = request_info;
+        let (id, request, queued_at) = request_info;
         let proto = request.protocol();
 
-        // accept outbound connections only if the handler is not deactivated
-        if matches!(self.state, HandlerState::Deactivated) {
-            self.events_out.push(Err(HandlerErr::Outbound {
-                error: RPCError::Disconnected,
-                proto,
-                id,
-            }));
+        if !self.accept_negotiated_outbound(id, proto) {
+            return;
         }
 
+        metrics::inc_counter_vec(&metrics::TOTAL_RPC_REQUESTS_SENT, &[proto.as_ref()]);
+
         // add the stream to substreams if we expect a response, otherwise drop the stream.
         let expected_responses = request.expected_responses();
         if expected_responses > 0 {
             // new outbound request. Store the stream and tag the output.
-            let delay_key = self.outbound_substreams_delay.insert(
-                self.current_outbound_substream_id,
-                Duration::from_secs(RESPONSE_TIMEOUT),
-            );
+            let delay_key = self
+                .outbound_substreams_delay
+                .insert(self.current_outbound_substream_id, self.resp_timeout);
             let awaiting_stream = OutboundSubstreamState::RequestPendingResponse {
                 substream: Box::new(out),
                 request,
@@ -373,6 +481,7 @@ where
                         proto,
                         remaining_chunks: expected_responses,
                         req_id: id,
+                        sent_at: Some(queued_at),
                     },
                 )
                 .is_some()
@@ -400,10 +509,9 @@ where
         if expected_responses > 0 {
             if self.inbound_substreams.len() < MAX_INBOUND_SUBSTREAMS {
                 // Store the stream and tag the output.
-                let delay_key = self.inbound_substreams_delay.insert(
-                    self.current_inbound_substream_id,
-                    Duration::from_secs(RESPONSE_TIMEOUT),
-                );
+                let delay_key = self
+                    .inbound_substreams_delay
+                    .insert(self.current_inbound_substream_id, self.resp_timeout);
                 let awaiting_stream = InboundState::Idle(substream);
                 self.inbound_substreams.insert(
                     self.current_inbound_substream_id,
@@ -443,9 +551,10 @@ where
 
     fn inject_event(&mut self, rpc_event: Self::InEvent) {
         match rpc_event {
-            RPCSend::Request(id, req) => self.send_request(id, req),
+            RPCSend::Request(id, req) => self.send_request(id, req, Instant::now()),
             RPCSend::Response(inbound_id, response) => self.send_response(inbound_id, response),
             RPCSend::Shutdown(id, reason) => self.shutdown(Some((id, reason))),
+            RPCSend::Cancel(id) => self.cancel_request(id),
         }
         // In any case, we need the handler to process the event.
         if let Some(waker) = &self.waker {
@@ -460,12 +569,18 @@ where
             <Self::OutboundProtocol as OutboundUpgrade<NegotiatedSubstream>>::Error,
         >,
     ) {
-        let (id, req) = request_info;
+        let (id, req, queued_at) = request_info;
+        if self.cancelled_requests.contains(&id) {
+            // The request was cancelled, there is no need to retry or report it.
+            self.dial_negotiated -= 1;
+            self.take_cancelled(&id);
+            return;
+        }
         if let ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply(RPCError::IoError(_))) = error
         {
             self.outbound_io_error_retries += 1;
             if self.outbound_io_error_retries < IO_ERROR_RETRIES {
-                self.send_request(id, req);
+                self.send_request(id, req, queued_at);
                 return;
             }
         }
@@ -503,6 +618,30 @@ where
         }));
     }
 
+    fn inject_listen_upgrade_error(
+        &mut self,
+        _info: Self::InboundOpenInfo,
+        error: ConnectionHandlerUpgrErr<
+            <Self::InboundProtocol as InboundUpgrade<NegotiatedSubstream>>::Error,
+        >,
+    ) {
+        // Only report requests that could not be read. Failing to negotiate a protocol is not
+        // the peer's fault, since peers can try protocols we don't support.
+        if let ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply((proto, error))) = error {
+            // The peer closed the substream without sending a request, or the transport failed
+            // while reading it. Neither says anything about the request, so don't report them.
+            if matches!(error, RPCError::IncompleteStream | RPCError::IoError(_)) {
+                return;
+            }
+            self.events_out.push(Err(HandlerErr::Inbound {
+                id: self.current_inbound_substream_id,
+                proto,
+                error,
+            }));
+            self.current_inbound_substream_id.0 += 1;
+        }
+    }
+
     fn connection_keep_alive(&self) -> KeepAlive {
         // Check that we don't have outbound items pending for dialing, nor dialing, nor
         // established. Also check that there are no established inbound substreams.
@@ -711,10 +850,10 @@ where
                                 info.remaining_chunks = info.remaining_chunks.saturating_sub(1);
 
                                 // If this substream has not ended, we reset the timer.
-                                // Each chunk is allowed RESPONSE_TIMEOUT to be sent.
+                                // Each chunk is allowed `resp_timeout` to be sent.
                                 if let Some(ref delay_key) = info.delay_key {
                                     self.inbound_substreams_delay
-                                        .reset(delay_key, Duration::from_secs(RESPONSE_TIMEOUT));
+                                        .reset(delay_key, self.resp_timeout);
                                 }
 
                                 // The stream may be currently idle. Attempt to process more
@@ -848,7 +987,7 @@ where
                                     };
                                 substream_entry.remaining_chunks = Some(remaining_chunks);
                                 self.outbound_substreams_delay
-                                    .reset(delay_key, Duration::from_secs(RESPONSE_TIMEOUT));
+                                    .reset(delay_key, self.resp_timeout);
                             }
                         } else {
                             // either this is a single response request or this response closes the
@@ -860,6 +999,16 @@ where
                         let id = entry.get().req_id;
                         let proto = entry.get().proto;
 
+                        if let Some(sent_at) = entry.get_mut().sent_at.take() {
+                            self.observe_response_time(proto, &id, sent_at);
+                        }
+                        if !matches!(response, RPCCodedResponse::StreamTermination(_)) {
+                            metrics::inc_counter_vec(
+                                &metrics::TOTAL_RPC_RESPONSES_RECEIVED,
+                                &[proto.as_ref()],
+                            );
+                        }
+
                         let received = match response {
                             RPCCodedResponse::StreamTermination(t) => {
                                 Ok(RPCReceived::EndOfStream(id, t))
@@ -960,7 +1109,7 @@ where
         // establish outbound substreams
         if !self.dial_queue.is_empty() && self.dial_negotiated < self.max_dial_negotiated {
             self.dial_negotiated += 1;
-            let (id, req) = self.dial_queue.remove(0);
+            let (id, req, queued_at) = self.dial_queue.remove(0);
             self.dial_queue.shrink_to_fit();
             return Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest {
                 protocol: SubstreamProtocol::new(
@@ -971,7 +1120,7 @@ where
                     },
                     (),
                 )
-                .map_info(|()| (id, req)),
+                .map_info(|()| (id, req, queued_at)),
             });
         }
 
@@ -1035,3 +1184,165 @@ async fn send_message_to_inbound_substream<TSpec: EthSpec>(
         send_result.map(|_| (substream, false))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::methods::Ping;
+    use std::marker::PhantomData;
+    use types::{Hash256, MainnetEthSpec, Slot};
+
+    type E = MainnetEthSpec;
+
+    fn build_handler() -> RPCHandler<u64, E> {
+        build_handler_with_slow_response_threshold(Duration::from_secs(SLOW_RESPONSE_THRESHOLD))
+    }
+
+    fn build_handler_with_slow_response_threshold(threshold: Duration) -> RPCHandler<u64, E> {
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let fork_context = Arc::new(ForkContext::new::<E>(
+            Slot::new(0),
+            Hash256::zero(),
+            &E::default_spec(),
+        ));
+        let listen_protocol = SubstreamProtocol::new(
+            RPCProtocol {
+                fork_context: fork_context.clone(),
+                max_rpc_size: max_rpc_size(&fork_context),
+                enable_light_client_server: false,
+                phantom: PhantomData,
+            },
+            (),
+        );
+        RPCHandler::new(
+            listen_protocol,
+            fork_context,
+            &log,
+            Duration::from_secs(RESPONSE_TIMEOUT),
+            threshold,
+        )
+    }
+
+    fn apply_error(
+        proto: Protocol,
+        error: RPCError,
+    ) -> ConnectionHandlerUpgrErr<(Protocol, RPCError)> {
+        ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Apply((proto, error)))
+    }
+
+    /// A request cancelled while its substream is being opened is not reported once the
+    /// negotiation completes.
+    #[tokio::test]
+    async fn test_cancel_request_while_negotiating() {
+        let mut handler = build_handler();
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        handler.inject_event(RPCSend::Request(1, OutboundRequest::Ping(Ping { data: 1 })));
+        handler.inject_event(RPCSend::Request(2, OutboundRequest::Ping(Ping { data: 2 })));
+        for _ in 0..2 {
+            assert!(matches!(
+                handler.poll(&mut cx),
+                Poll::Ready(ConnectionHandlerEvent::OutboundSubstreamRequest { .. })
+            ));
+        }
+        assert_eq!(handler.dial_negotiated, 2);
+
+        handler.inject_event(RPCSend::Cancel(1));
+        assert_eq!(handler.cancelled_requests.as_slice(), &[1]);
+
+        // The cancelled request's negotiation fails without being reported, the other one is.
+        handler.inject_dial_upgrade_error(
+            (1, OutboundRequest::Ping(Ping { data: 1 }), Instant::now()),
+            ConnectionHandlerUpgrErr::Timeout,
+        );
+        assert!(handler.events_out.is_empty());
+        handler.inject_dial_upgrade_error(
+            (2, OutboundRequest::Ping(Ping { data: 2 }), Instant::now()),
+            ConnectionHandlerUpgrErr::Timeout,
+        );
+        assert!(matches!(
+            handler.events_out.as_slice(),
+            [Err(HandlerErr::Outbound {
+                id: 2,
+                error: RPCError::NegotiationTimeout,
+                ..
+            })]
+        ));
+        assert_eq!(handler.dial_negotiated, 0);
+
+        // Cancelling a request that is not in flight is not recorded.
+        handler.inject_event(RPCSend::Cancel(3));
+        assert!(handler.cancelled_requests.is_empty());
+    }
+
+    /// Only inbound requests that the peer sent but we could not read are reported.
+    #[tokio::test]
+    async fn test_listen_upgrade_errors() {
+        let mut handler = build_handler();
+
+        handler.inject_listen_upgrade_error((), ConnectionHandlerUpgrErr::Timeout);
+        handler.inject_listen_upgrade_error(
+            (),
+            ConnectionHandlerUpgrErr::Upgrade(UpgradeError::Select(NegotiationError::Failed)),
+        );
+        handler.inject_listen_upgrade_error(
+            (),
+            apply_error(Protocol::Status, RPCError::IncompleteStream),
+        );
+        handler.inject_listen_upgrade_error(
+            (),
+            apply_error(Protocol::Status, RPCError::IoError("reset".into())),
+        );
+        assert!(handler.events_out.is_empty());
+
+        handler.inject_listen_upgrade_error(
+            (),
+            apply_error(
+                Protocol::BlocksByRange,
+                RPCError::InvalidData("malformed".into()),
+            ),
+        );
+        assert!(matches!(
+            handler.events_out.as_slice(),
+            [Err(HandlerErr::Inbound {
+                id: SubstreamId(0),
+                proto: Protocol::BlocksByRange,
+                error: RPCError::InvalidData(_),
+            })]
+        ));
+        assert_eq!(handler.current_inbound_substream_id, SubstreamId(1));
+    }
+
+    /// Responses arriving after the configured threshold are flagged as slow.
+    #[test]
+    fn test_slow_response_threshold() {
+        let sent_at = Instant::now() - Duration::from_millis(50);
+
+        let handler = build_handler_with_slow_response_threshold(Duration::from_millis(1));
+        assert!(handler.observe_response_time(Protocol::Status, &1, sent_at));
+
+        let handler = build_handler();
+        assert!(!handler.observe_response_time(Protocol::Status, &1, sent_at));
+    }
+
+    /// A substream negotiated after the handler was deactivated is dropped and its request fails
+    /// with `Disconnected`.
+    #[test]
+    fn test_negotiated_outbound_on_deactivated_handler() {
+        let mut handler = build_handler();
+        assert!(handler.accept_negotiated_outbound(1, Protocol::Ping));
+        assert!(handler.events_out.is_empty());
+
+        handler.state = HandlerState::Deactivated;
+        assert!(!handler.accept_negotiated_outbound(2, Protocol::Ping));
+        assert!(matches!(
+            handler.events_out.as_slice(),
+            [Err(HandlerErr::Outbound {
+                id: 2,
+                proto: Protocol::Ping,
+                error: RPCError::Disconnected,
+            })]
+        ));
+    }
+}
@@ -111,7 +111,8 @@ pub struct MetaData<T: EthSpec> {
 /// Note: any unknown `u64::into(n)` will resolve to `Goodbye::Unknown` for any unknown `n`,
 /// however `GoodbyeReason::Unknown.into()` will go into `0_u64`. Therefore de-serializing then
 /// re-serializing may not return the same bytes.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum GoodbyeReason {
     /// This node has shutdown.
     ClientShutdown = 1,
@@ -223,6 +224,31 @@ pub struct OldBlocksByRangeRequest {
     pub step: u64,
 }
 
+impl BlocksByRangeRequest {
+    /// The slot following the last requested slot. Saturates so that a request starting near
+    /// the last slot can't overflow.
+    pub fn end_slot(&self) -> u64 {
+        self.start_slot.saturating_add(self.count)
+    }
+}
+
+impl OldBlocksByRangeRequest {
+    /// Returns the request served for this request, or `None` if the step is zero and the
+    /// request is invalid. A step above one is only served its first block, see
+    /// https://github.com/ethereum/consensus-specs/pull/2856.
+    pub fn to_blocks_by_range_request(&self) -> Option<BlocksByRangeRequest> {
+        let count = match self.step {
+            0 => return None,
+            1 => self.count,
+            _ => 1,
+        };
+        Some(BlocksByRangeRequest {
+            start_slot: self.start_slot,
+            count,
+        })
+    }
+}
+
 /// Request a number of beacon block bodies from a peer.
 #[derive(Clone, Debug, PartialEq)]
 pub struct BlocksByRootRequest {
@@ -467,3 +493,56 @@ impl slog::KV for StatusMessage {
         slog::Result::Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn old_request(start_slot: u64, count: u64, step: u64) -> OldBlocksByRangeRequest {
+        OldBlocksByRangeRequest {
+            start_slot,
+            count,
+            step,
+        }
+    }
+
+    #[test]
+    fn test_blocks_by_range_request_with_zero_step() {
+        assert_eq!(old_request(10, 5, 0).to_blocks_by_range_request(), None);
+    }
+
+    #[test]
+    fn test_blocks_by_range_request_overflowing_range() {
+        // `start_slot + count * step` overflows, only the first block is served.
+        let req = old_request(u64::MAX - 1, u64::MAX, 3)
+            .to_blocks_by_range_request()
+            .unwrap();
+        assert_eq!(req.count, 1);
+        assert_eq!(req.end_slot(), u64::MAX);
+
+        // `start_slot + count` overflows, the range ends at the last slot.
+        let req = old_request(u64::MAX - 1, MAX_REQUEST_BLOCKS, 1)
+            .to_blocks_by_range_request()
+            .unwrap();
+        assert_eq!(req.count, MAX_REQUEST_BLOCKS);
+        assert_eq!(req.end_slot(), u64::MAX);
+
+        let req = old_request(u64::MAX, 1, 2)
+            .to_blocks_by_range_request()
+            .unwrap();
+        assert_eq!(req.end_slot(), u64::MAX);
+    }
+
+    #[test]
+    fn test_blocks_by_range_request_valid_range() {
+        let req = old_request(64, 32, 1).to_blocks_by_range_request().unwrap();
+        assert_eq!(
+            req,
+            BlocksByRangeRequest {
+                start_slot: 64,
+                count: 32
+            }
+        );
+        assert_eq!(req.end_slot(), 96);
+    }
+}
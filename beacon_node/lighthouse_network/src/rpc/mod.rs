@@ -4,16 +4,20 @@
 //! direct peer-to-peer communication primarily for sending/receiving chain information for
 //! syncing.
 
+use crate::metrics;
 use futures::future::FutureExt;
-use handler::{HandlerEvent, RPCHandler};
+use handler::{HandlerEvent, RPCHandler, RESPONSE_TIMEOUT, SLOW_RESPONSE_THRESHOLD};
 use libp2p::core::connection::ConnectionId;
+use libp2p::swarm::behaviour::{ConnectionClosed, ConnectionEstablished, DialFailure, FromSwarm};
 use libp2p::swarm::{
     handler::ConnectionHandler, NetworkBehaviour, NetworkBehaviourAction, NotifyHandler,
     PollParameters, SubstreamProtocol,
 };
 use libp2p::PeerId;
+use protocol::INBOUND_NEGOTIATION_TIMEOUT;
 use rate_limiter::{RPCRateLimiter as RateLimiter, RateLimitedErr};
-use slog::{crit, debug, o};
+use slog::{crit, debug, o, warn};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::task::{Context, Poll};
@@ -32,7 +36,7 @@ pub use methods::{
 pub(crate) use outbound::OutboundRequest;
 pub use protocol::{max_rpc_size, Protocol, RPCError};
 
-use self::config::OutboundRateLimiterConfig;
+use self::config::{InboundRateLimiterConfig, OutboundRateLimiterConfig};
 use self::self_limiter::SelfRateLimiter;
 
 pub(crate) mod codec;
@@ -44,9 +48,17 @@ mod protocol;
 mod rate_limiter;
 mod self_limiter;
 
+/// Maximum number of requests buffered for a peer that is being dialed.
+const MAX_PENDING_REQUESTS_PER_PEER: usize = 16;
+
+/// The connection reported for requests that fail before any connection to the peer exists.
+fn no_connection() -> ConnectionId {
+    ConnectionId::new(0)
+}
+
 /// Composite trait for a request id.
-pub trait ReqId: Send + 'static + std::fmt::Debug + Copy + Clone {}
-impl<T> ReqId for T where T: Send + 'static + std::fmt::Debug + Copy + Clone {}
+pub trait ReqId: Send + 'static + std::fmt::Debug + Copy + Clone + PartialEq {}
+impl<T> ReqId for T where T: Send + 'static + std::fmt::Debug + Copy + Clone + PartialEq {}
 
 /// RPC events sent from Lighthouse.
 #[derive(Debug, Clone)]
@@ -64,6 +76,8 @@ pub enum RPCSend<Id, TSpec: EthSpec> {
     Response(SubstreamId, RPCCodedResponse<TSpec>),
     /// Lighthouse has requested to terminate the connection with a goodbye message.
     Shutdown(Id, GoodbyeReason),
+    /// Lighthouse is no longer interested in the response to the request with the given `Id`.
+    Cancel(Id),
 }
 
 /// RPC events received from outside Lighthouse.
@@ -90,6 +104,7 @@ impl<T: EthSpec, Id: std::fmt::Debug> std::fmt::Display for RPCSend<Id, T> {
             RPCSend::Request(id, req) => write!(f, "RPC Request(id: {:?}, {})", id, req),
             RPCSend::Response(id, res) => write!(f, "RPC Response(id: {:?}, {})", id, res),
             RPCSend::Shutdown(_id, reason) => write!(f, "Sending Goodbye: {}", reason),
+            RPCSend::Cancel(id) => write!(f, "Cancel RPC Request(id: {:?})", id),
         }
     }
 }
@@ -108,6 +123,28 @@ pub struct RPCMessage<Id, TSpec: EthSpec> {
 type BehaviourAction<Id, TSpec> =
     NetworkBehaviourAction<RPCMessage<Id, TSpec>, RPCHandler<Id, TSpec>>;
 
+/// Timing parameters used by the RPC behaviour and its connection handlers.
+#[derive(Debug, Clone)]
+pub struct NetworkParams {
+    /// The time a substream awaiting a response chunk is kept open before it times out.
+    pub resp_timeout: Duration,
+    /// The time after which the first response chunk to a request is logged as slow.
+    pub slow_response_threshold: Duration,
+    /// The time an inbound substream has to negotiate a protocol and deliver its request before
+    /// it is dropped.
+    pub inbound_negotiation_timeout: Duration,
+}
+
+impl Default for NetworkParams {
+    fn default() -> Self {
+        NetworkParams {
+            resp_timeout: Duration::from_secs(RESPONSE_TIMEOUT),
+            slow_response_threshold: Duration::from_secs(SLOW_RESPONSE_THRESHOLD),
+            inbound_negotiation_timeout: Duration::from_secs(INBOUND_NEGOTIATION_TIMEOUT),
+        }
+    }
+}
+
 /// Implements the libp2p `NetworkBehaviour` trait and therefore manages network-level
 /// logic.
 pub struct RPC<Id: ReqId, TSpec: EthSpec> {
@@ -116,9 +153,16 @@ pub struct RPC<Id: ReqId, TSpec: EthSpec> {
     /// Rate limiter for our own requests.
     self_limiter: Option<SelfRateLimiter<Id, TSpec>>,
     /// Queue of events to be processed.
-    events: Vec<BehaviourAction<Id, TSpec>>,
+    events: VecDeque<BehaviourAction<Id, TSpec>>,
     fork_context: Arc<ForkContext>,
     enable_light_client_server: bool,
+    /// Timing parameters handed to every connection handler.
+    network_params: NetworkParams,
+    /// Established connections of the peers with at least one.
+    connected_peers: HashMap<PeerId, HashSet<ConnectionId>>,
+    /// Requests submitted for peers that are being dialed. These are sent once the peer connects
+    /// and fail if the dial does.
+    pending_requests: HashMap<PeerId, Vec<(Id, OutboundRequest<TSpec>)>>,
     /// Slog logger for RPC behaviour.
     log: slog::Logger,
 }
@@ -127,24 +171,15 @@ impl<Id: ReqId, TSpec: EthSpec> RPC<Id, TSpec> {
     pub fn new(
         fork_context: Arc<ForkContext>,
         enable_light_client_server: bool,
+        inbound_rate_limiter_config: InboundRateLimiterConfig,
         outbound_rate_limiter_config: Option<OutboundRateLimiterConfig>,
+        network_params: NetworkParams,
         log: slog::Logger,
     ) -> Self {
         let log = log.new(o!("service" => "libp2p_rpc"));
 
-        let limiter = RateLimiter::builder()
-            .n_every(Protocol::MetaData, 2, Duration::from_secs(5))
-            .n_every(Protocol::Ping, 2, Duration::from_secs(10))
-            .n_every(Protocol::Status, 5, Duration::from_secs(15))
-            .one_every(Protocol::Goodbye, Duration::from_secs(10))
-            .one_every(Protocol::LightClientBootstrap, Duration::from_secs(10))
-            .n_every(
-                Protocol::BlocksByRange,
-                methods::MAX_REQUEST_BLOCKS,
-                Duration::from_secs(10),
-            )
-            .n_every(Protocol::BlocksByRoot, 128, Duration::from_secs(10))
-            .build()
+        debug!(log, "Using inbound rate limiting params"; "config" => ?inbound_rate_limiter_config);
+        let limiter = RateLimiter::new_with_config(inbound_rate_limiter_config)
             .expect("Configuration parameters are valid");
 
         let self_limiter = outbound_rate_limiter_config.map(|config| {
@@ -154,9 +189,12 @@ impl<Id: ReqId, TSpec: EthSpec> RPC<Id, TSpec> {
         RPC {
             limiter,
             self_limiter,
-            events: Vec::new(),
+            events: VecDeque::new(),
             fork_context,
             enable_light_client_server,
+            network_params,
+            connected_peers: HashMap::new(),
+            pending_requests: HashMap::new(),
             log,
         }
     }
@@ -170,17 +208,80 @@ impl<Id: ReqId, TSpec: EthSpec> RPC<Id, TSpec> {
         id: (ConnectionId, SubstreamId),
         event: RPCCodedResponse<TSpec>,
     ) {
-        self.events.push(NetworkBehaviourAction::NotifyHandler {
-            peer_id,
-            handler: NotifyHandler::One(id.0),
-            event: RPCSend::Response(id.1, event),
-        });
+        self.events
+            .push_back(NetworkBehaviourAction::NotifyHandler {
+                peer_id,
+                handler: NotifyHandler::One(id.0),
+                event: RPCSend::Response(id.1, event),
+            });
     }
 
     /// Submits an RPC request.
     ///
-    /// The peer must be connected for this to succeed.
+    /// If a dial to the peer is in flight (see [`RPC::dialing_peer`]), the request is buffered and
+    /// sent once the peer connects. A request for any other peer that is not connected fails with
+    /// [`RPCError::Disconnected`].
     pub fn send_request(&mut self, peer_id: PeerId, request_id: Id, req: OutboundRequest<TSpec>) {
+        if self.connected_peers.contains_key(&peer_id) {
+            self.dispatch_request(peer_id, request_id, req);
+            return;
+        }
+
+        match self.pending_requests.get_mut(&peer_id) {
+            Some(pending_requests) if pending_requests.len() < MAX_PENDING_REQUESTS_PER_PEER => {
+                debug!(self.log, "Queueing request until the peer connects";
+                    "peer_id" => %peer_id, "request" => %req);
+                pending_requests.push((request_id, req));
+            }
+            Some(pending_requests) => {
+                warn!(self.log, "Dropping request for peer that is not connected";
+                    "peer_id" => %peer_id, "request" => %req, "pending_requests" => pending_requests.len());
+                self.fail_request(peer_id, no_connection(), request_id, req.protocol());
+            }
+            None => {
+                debug!(self.log, "Request for peer that is neither connected nor being dialed";
+                    "peer_id" => %peer_id, "request" => %req);
+                self.fail_request(peer_id, no_connection(), request_id, req.protocol());
+            }
+        }
+    }
+
+    /// Informs the behaviour that a dial to the peer is in flight. Requests submitted for the
+    /// peer are buffered until the dial completes instead of failing.
+    pub fn dialing_peer(&mut self, peer_id: PeerId) {
+        if !self.connected_peers.contains_key(&peer_id) {
+            self.pending_requests.entry(peer_id).or_default();
+        }
+    }
+
+    /// Reports to the user that a request will never be sent to the peer.
+    fn fail_request(&mut self, peer_id: PeerId, conn_id: ConnectionId, id: Id, proto: Protocol) {
+        self.events
+            .push_back(NetworkBehaviourAction::GenerateEvent(RPCMessage {
+                peer_id,
+                conn_id,
+                event: Err(HandlerErr::Outbound {
+                    id,
+                    proto,
+                    error: RPCError::Disconnected,
+                }),
+            }));
+    }
+
+    /// Fails all the requests buffered for the peer.
+    fn fail_pending_requests(&mut self, peer_id: PeerId, conn_id: ConnectionId) {
+        if let Some(pending_requests) = self.pending_requests.remove(&peer_id) {
+            debug!(self.log, "Dropping requests of peer that is not connected";
+                "peer_id" => %peer_id, "requests" => pending_requests.len());
+            for (request_id, req) in pending_requests {
+                self.fail_request(peer_id, conn_id, request_id, req.protocol());
+            }
+        }
+    }
+
+    /// Hands a request for a connected peer over to the self rate limiter, if any, or directly to
+    /// a connection handler.
+    fn dispatch_request(&mut self, peer_id: PeerId, request_id: Id, req: OutboundRequest<TSpec>) {
         let event = if let Some(self_limiter) = self.self_limiter.as_mut() {
             match self_limiter.allows(peer_id, request_id, req) {
                 Ok(event) => event,
@@ -197,17 +298,122 @@ impl<Id: ReqId, TSpec: EthSpec> RPC<Id, TSpec> {
             }
         };
 
-        self.events.push(event);
+        self.events.push_back(event);
+    }
+
+    /// Cancels a request sent to a peer. A request that has not been sent yet is dropped,
+    /// otherwise the handler drops the request's substream and no further responses are reported
+    /// for it. Cancelling a request that has already completed has no effect.
+    ///
+    /// Requests are identified by `request_id` only, so it must be unique among the requests in
+    /// flight to the peer. Every request sharing the id is cancelled.
+    pub fn cancel_request(&mut self, peer_id: PeerId, request_id: Id) {
+        if let Some(pending_requests) = self.pending_requests.get_mut(&peer_id) {
+            pending_requests.retain(|(id, _)| *id != request_id);
+            if pending_requests.is_empty() {
+                self.pending_requests.remove(&peer_id);
+            }
+        }
+        if let Some(limiter) = self.self_limiter.as_mut() {
+            limiter.cancel(peer_id, request_id);
+        }
+        // Drop the request if it hasn't reached the handler yet.
+        self.events.retain(|event| {
+            !matches!(event, NetworkBehaviourAction::NotifyHandler {
+                    peer_id: p,
+                    event: RPCSend::Request(id, _),
+                    ..
+                } if *p == peer_id && *id == request_id)
+        });
+        // The request may have been sent on any of the peer's connections.
+        for conn_id in self.connected_peers.get(&peer_id).into_iter().flatten() {
+            self.events
+                .push_back(NetworkBehaviourAction::NotifyHandler {
+                    peer_id,
+                    handler: NotifyHandler::One(*conn_id),
+                    event: RPCSend::Cancel(request_id),
+                });
+        }
     }
 
     /// Lighthouse wishes to disconnect from this peer by sending a Goodbye message. This
     /// gracefully terminates the RPC behaviour with a goodbye message.
     pub fn shutdown(&mut self, peer_id: PeerId, id: Id, reason: GoodbyeReason) {
-        self.events.push(NetworkBehaviourAction::NotifyHandler {
-            peer_id,
-            handler: NotifyHandler::Any,
-            event: RPCSend::Shutdown(id, reason),
-        });
+        metrics::inc_counter_vec(&metrics::TOTAL_RPC_GOODBYES, &[(&reason).into(), "sent"]);
+        self.events
+            .push_back(NetworkBehaviourAction::NotifyHandler {
+                peer_id,
+                handler: NotifyHandler::Any,
+                event: RPCSend::Shutdown(id, reason),
+            });
+    }
+
+    /// Returns an iterator over the peers this behaviour currently has a connection to.
+    pub fn connected_peers(&self) -> impl Iterator<Item = &PeerId> {
+        self.connected_peers.keys()
+    }
+
+    /// Returns true if this behaviour currently has a connection to the peer.
+    pub fn is_connected(&self, peer_id: &PeerId) -> bool {
+        self.connected_peers.contains_key(peer_id)
+    }
+
+    /// The first connection to a peer was established. Sends any requests that were submitted
+    /// before the peer connected.
+    fn on_peer_connected(&mut self, peer_id: PeerId) {
+        if let Some(pending_requests) = self.pending_requests.remove(&peer_id) {
+            debug!(self.log, "Sending requests queued before the peer connected";
+                "peer_id" => %peer_id, "requests" => pending_requests.len());
+            for (request_id, req) in pending_requests {
+                self.dispatch_request(peer_id, request_id, req);
+            }
+        }
+    }
+
+    /// The last connection to a peer was closed. Requests that were still waiting to be sent to
+    /// this peer will never reach a handler, so they are reported as failed instead.
+    fn on_peer_disconnected(&mut self, peer_id: PeerId, conn_id: ConnectionId) {
+        self.connected_peers.remove(&peer_id);
+        self.fail_pending_requests(peer_id, conn_id);
+
+        // Get a list of pending requests from the self rate limiter
+        if let Some(limiter) = self.self_limiter.as_mut() {
+            for (id, proto) in limiter.peer_disconnected(peer_id) {
+                self.events
+                    .push_back(NetworkBehaviourAction::GenerateEvent(RPCMessage {
+                        peer_id,
+                        conn_id,
+                        event: Err(HandlerErr::Outbound {
+                            id,
+                            proto,
+                            error: RPCError::Disconnected,
+                        }),
+                    }));
+            }
+        }
+
+        // Replace the pending requests to the disconnected peer with reports of failed requests.
+        for event in self.events.iter_mut() {
+            if let NetworkBehaviourAction::NotifyHandler {
+                peer_id: p,
+                event: RPCSend::Request(request_id, req),
+                ..
+            } = event
+            {
+                if *p == peer_id {
+                    let failed = NetworkBehaviourAction::GenerateEvent(RPCMessage {
+                        peer_id,
+                        conn_id,
+                        event: Err(HandlerErr::Outbound {
+                            id: *request_id,
+                            proto: req.protocol(),
+                            error: RPCError::Disconnected,
+                        }),
+                    });
+                    *event = failed;
+                }
+            }
+        }
     }
 }
 
@@ -229,9 +435,12 @@ where
                     phantom: PhantomData,
                 },
                 (),
-            ),
+            )
+            .with_timeout(self.network_params.inbound_negotiation_timeout),
             self.fork_context.clone(),
             &self.log,
+            self.network_params.resp_timeout,
+            self.network_params.slow_response_threshold,
         )
     }
 
@@ -242,12 +451,18 @@ where
         event: <Self::ConnectionHandler as ConnectionHandler>::OutEvent,
     ) {
         if let Ok(RPCReceived::Request(ref id, ref req)) = event {
+            if let InboundRequest::Goodbye(reason) = req {
+                metrics::inc_counter_vec(
+                    &metrics::TOTAL_RPC_GOODBYES,
+                    &[reason.into(), "received"],
+                );
+            }
             // check if the request is conformant to the quota
             match self.limiter.allows(&peer_id, req) {
                 Ok(()) => {
                     // send the event to the user
                     self.events
-                        .push(NetworkBehaviourAction::GenerateEvent(RPCMessage {
+                        .push_back(NetworkBehaviourAction::GenerateEvent(RPCMessage {
                             peer_id,
                             conn_id,
                             event,
@@ -289,7 +504,7 @@ where
             }
         } else {
             self.events
-                .push(NetworkBehaviourAction::GenerateEvent(RPCMessage {
+                .push_back(NetworkBehaviourAction::GenerateEvent(RPCMessage {
                     peer_id,
                     conn_id,
                     event,
@@ -297,6 +512,58 @@ where
         }
     }
 
+    fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
+        match event {
+            FromSwarm::ConnectionClosed(ConnectionClosed {
+                peer_id,
+                connection_id,
+                remaining_established,
+                ..
+            }) => {
+                if let Some(connections) = self.connected_peers.get_mut(&peer_id) {
+                    connections.remove(&connection_id);
+                }
+                if remaining_established == 0 {
+                    self.on_peer_disconnected(peer_id, connection_id)
+                }
+            }
+            FromSwarm::ConnectionEstablished(ConnectionEstablished {
+                peer_id,
+                connection_id,
+                other_established,
+                ..
+            }) => {
+                self.connected_peers
+                    .entry(peer_id)
+                    .or_default()
+                    .insert(connection_id);
+                if other_established == 0 {
+                    self.on_peer_connected(peer_id)
+                }
+            }
+            FromSwarm::DialFailure(DialFailure {
+                peer_id: Some(peer_id),
+                ..
+            }) => {
+                // The requests buffered for this peer will never be sent.
+                self.fail_pending_requests(peer_id, no_connection())
+            }
+            FromSwarm::DialFailure(_)
+            | FromSwarm::AddressChange(_)
+            | FromSwarm::ListenFailure(_)
+            | FromSwarm::NewListener(_)
+            | FromSwarm::NewListenAddr(_)
+            | FromSwarm::ExpiredListenAddr(_)
+            | FromSwarm::ListenerError(_)
+            | FromSwarm::ListenerClosed(_)
+            | FromSwarm::NewExternalAddr(_)
+            | FromSwarm::ExpiredExternalAddr(_) => {
+                // Rpc Behaviour does not act on these swarm events. We use a comprehensive match
+                // statement to ensure future events are dealt with appropriately.
+            }
+        }
+    }
+
     fn poll(
         &mut self,
         cx: &mut Context,
@@ -307,12 +574,12 @@ where
 
         if let Some(self_limiter) = self.self_limiter.as_mut() {
             if let Poll::Ready(event) = self_limiter.poll_ready(cx) {
-                self.events.push(event)
+                self.events.push_back(event)
             }
         }
 
-        if !self.events.is_empty() {
-            return Poll::Ready(self.events.remove(0));
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(event);
         }
 
         Poll::Pending
@@ -353,3 +620,379 @@ where
         slog::Result::Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::core::ConnectedPoint;
+    use libp2p::swarm::AddressRecord;
+    use libp2p::Multiaddr;
+    use types::{Hash256, MainnetEthSpec, Slot};
+
+    type E = MainnetEthSpec;
+
+    /// Poll parameters of a swarm without any listeners or external addresses.
+    struct TestPollParameters(PeerId);
+
+    impl PollParameters for TestPollParameters {
+        type SupportedProtocolsIter = std::iter::Empty<Vec<u8>>;
+        type ListenedAddressesIter = std::iter::Empty<Multiaddr>;
+        type ExternalAddressesIter = std::iter::Empty<AddressRecord>;
+
+        fn supported_protocols(&self) -> Self::SupportedProtocolsIter {
+            std::iter::empty()
+        }
+
+        fn listened_addresses(&self) -> Self::ListenedAddressesIter {
+            std::iter::empty()
+        }
+
+        fn external_addresses(&self) -> Self::ExternalAddressesIter {
+            std::iter::empty()
+        }
+
+        fn local_peer_id(&self) -> &PeerId {
+            &self.0
+        }
+    }
+
+    fn build_rpc() -> RPC<u64, E> {
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let fork_context = Arc::new(ForkContext::new::<E>(
+            Slot::new(0),
+            Hash256::zero(),
+            &E::default_spec(),
+        ));
+        RPC::new(
+            fork_context,
+            false,
+            InboundRateLimiterConfig::default(),
+            None,
+            NetworkParams::default(),
+            log,
+        )
+    }
+
+    fn endpoint() -> ConnectedPoint {
+        ConnectedPoint::Listener {
+            local_addr: "/ip4/127.0.0.1/tcp/9000".parse().unwrap(),
+            send_back_addr: "/ip4/127.0.0.1/tcp/9001".parse().unwrap(),
+        }
+    }
+
+    fn connect(rpc: &mut RPC<u64, E>, peer_id: PeerId, connection_id: ConnectionId) {
+        rpc.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+            peer_id,
+            connection_id,
+            endpoint: &endpoint(),
+            failed_addresses: &[],
+            other_established: 0,
+        }));
+    }
+
+    /// Returns the ids of the requests reported as failed because the peer is not connected.
+    fn disconnected_errors(rpc: &mut RPC<u64, E>, peer_id: PeerId) -> Vec<u64> {
+        rpc.events
+            .drain(..)
+            .map(|event| match event {
+                NetworkBehaviourAction::GenerateEvent(RPCMessage {
+                    peer_id: p,
+                    event:
+                        Err(HandlerErr::Outbound {
+                            id,
+                            error: RPCError::Disconnected,
+                            ..
+                        }),
+                    ..
+                }) if p == peer_id => id,
+                _ => panic!("Expected a disconnected error for the peer"),
+            })
+            .collect()
+    }
+
+    /// Requests submitted while the peer is being dialed are sent once it connects.
+    #[tokio::test]
+    async fn test_request_before_connect_is_sent_on_connect() {
+        let mut rpc = build_rpc();
+        let peer_id = PeerId::random();
+
+        rpc.dialing_peer(peer_id);
+        rpc.send_request(peer_id, 1, OutboundRequest::Ping(Ping { data: 1 }));
+        rpc.send_request(peer_id, 2, OutboundRequest::Ping(Ping { data: 2 }));
+        assert!(rpc.events.is_empty());
+
+        connect(&mut rpc, peer_id, ConnectionId::new(1));
+
+        let sent_ids = rpc
+            .events
+            .iter()
+            .map(|event| match event {
+                NetworkBehaviourAction::NotifyHandler {
+                    peer_id: p,
+                    event: RPCSend::Request(id, OutboundRequest::Ping(_)),
+                    ..
+                } if *p == peer_id => *id,
+                _ => panic!("Expected a ping request to the connected peer"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sent_ids, vec![1, 2]);
+        assert!(rpc.pending_requests.is_empty());
+    }
+
+    /// The number of requests buffered for a peer that is being dialed is bounded, and the
+    /// requests over the bound fail.
+    #[tokio::test]
+    async fn test_pending_requests_are_bounded() {
+        let mut rpc = build_rpc();
+        let peer_id = PeerId::random();
+        let max_pending = MAX_PENDING_REQUESTS_PER_PEER as u64;
+
+        rpc.dialing_peer(peer_id);
+        for id in 0..(max_pending + 5) {
+            rpc.send_request(peer_id, id, OutboundRequest::Ping(Ping { data: id }));
+        }
+        assert_eq!(
+            rpc.pending_requests.get(&peer_id).map(Vec::len),
+            Some(MAX_PENDING_REQUESTS_PER_PEER)
+        );
+        assert_eq!(
+            disconnected_errors(&mut rpc, peer_id),
+            (max_pending..max_pending + 5).collect::<Vec<_>>()
+        );
+
+        connect(&mut rpc, peer_id, ConnectionId::new(1));
+        assert_eq!(rpc.events.len(), MAX_PENDING_REQUESTS_PER_PEER);
+    }
+
+    /// Requests for a peer that is neither connected nor being dialed fail straight away.
+    #[tokio::test]
+    async fn test_request_to_unknown_peer_fails() {
+        let mut rpc = build_rpc();
+        let peer_id = PeerId::random();
+
+        rpc.send_request(peer_id, 1, OutboundRequest::Ping(Ping { data: 1 }));
+        assert!(rpc.pending_requests.is_empty());
+        assert_eq!(disconnected_errors(&mut rpc, peer_id), vec![1]);
+    }
+
+    /// Requests buffered for a peer fail when the dial to it fails.
+    #[tokio::test]
+    async fn test_dial_failure_fails_pending_requests() {
+        let mut rpc = build_rpc();
+        let peer_id = PeerId::random();
+
+        rpc.dialing_peer(peer_id);
+        rpc.send_request(peer_id, 1, OutboundRequest::Ping(Ping { data: 1 }));
+        rpc.send_request(peer_id, 2, OutboundRequest::Ping(Ping { data: 2 }));
+        assert!(rpc.events.is_empty());
+
+        let handler = rpc.new_handler();
+        rpc.on_swarm_event(FromSwarm::DialFailure(DialFailure {
+            peer_id: Some(peer_id),
+            handler,
+            error: &libp2p::swarm::DialError::Banned,
+        }));
+        assert!(rpc.pending_requests.is_empty());
+        assert_eq!(disconnected_errors(&mut rpc, peer_id), vec![1, 2]);
+
+        // Once the dial has failed, new requests are no longer buffered.
+        rpc.send_request(peer_id, 3, OutboundRequest::Ping(Ping { data: 3 }));
+        assert_eq!(disconnected_errors(&mut rpc, peer_id), vec![3]);
+    }
+
+    /// Requests that have not reached a handler fail when the peer disconnects.
+    #[tokio::test]
+    async fn test_disconnect_fails_unsent_requests() {
+        let mut rpc = build_rpc();
+        let peer_id = PeerId::random();
+
+        connect(&mut rpc, peer_id, ConnectionId::new(1));
+        rpc.send_request(peer_id, 1, OutboundRequest::Ping(Ping { data: 1 }));
+        rpc.on_peer_disconnected(peer_id, ConnectionId::new(1));
+        assert_eq!(disconnected_errors(&mut rpc, peer_id), vec![1]);
+    }
+
+    /// A request that has not reached a handler when the connection closes is reported by `poll`
+    /// as failed, under the id it was submitted with.
+    #[tokio::test]
+    async fn test_request_fails_when_connection_closes() {
+        let mut rpc = build_rpc();
+        let peer_id = PeerId::random();
+        let connection_id = ConnectionId::new(1);
+
+        connect(&mut rpc, peer_id, connection_id);
+        rpc.send_request(peer_id, 7, OutboundRequest::Ping(Ping { data: 1 }));
+
+        let handler = rpc.new_handler();
+        rpc.on_swarm_event(FromSwarm::ConnectionClosed(ConnectionClosed {
+            peer_id,
+            connection_id,
+            endpoint: &endpoint(),
+            handler,
+            remaining_established: 0,
+        }));
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut params = TestPollParameters(PeerId::random());
+        match rpc.poll(&mut cx, &mut params) {
+            Poll::Ready(NetworkBehaviourAction::GenerateEvent(RPCMessage {
+                peer_id: p,
+                conn_id,
+                event:
+                    Err(HandlerErr::Outbound {
+                        id,
+                        proto,
+                        error: RPCError::Disconnected,
+                    }),
+            })) => {
+                assert_eq!(p, peer_id);
+                assert_eq!(conn_id, connection_id);
+                assert_eq!(id, 7);
+                assert_eq!(proto, Protocol::Ping);
+            }
+            _ => panic!("Expected the request to fail as disconnected"),
+        }
+        assert!(rpc.poll(&mut cx, &mut params).is_pending());
+    }
+
+    /// The connected peers are tracked across connection and disconnection of peers.
+    #[tokio::test]
+    async fn test_connected_peers() {
+        let mut rpc = build_rpc();
+        let peer_a = PeerId::random();
+        let peer_b = PeerId::random();
+        assert_eq!(rpc.connected_peers().count(), 0);
+        assert!(!rpc.is_connected(&peer_a));
+
+        connect(&mut rpc, peer_a, ConnectionId::new(1));
+        connect(&mut rpc, peer_b, ConnectionId::new(2));
+        assert!(rpc.is_connected(&peer_a));
+        assert!(rpc.is_connected(&peer_b));
+        assert_eq!(
+            rpc.connected_peers().copied().collect::<HashSet<_>>(),
+            HashSet::from([peer_a, peer_b])
+        );
+
+        rpc.on_peer_disconnected(peer_a, ConnectionId::new(1));
+        assert!(!rpc.is_connected(&peer_a));
+        assert!(rpc.is_connected(&peer_b));
+        assert_eq!(rpc.connected_peers().collect::<Vec<_>>(), vec![&peer_b]);
+
+        // Disconnecting a peer that is not connected is a no-op.
+        rpc.on_peer_disconnected(peer_a, ConnectionId::new(1));
+        assert_eq!(rpc.connected_peers().count(), 1);
+    }
+
+    /// Cancelling drops requests that have not reached the handler yet and tells the handler to
+    /// drop the rest.
+    #[tokio::test]
+    async fn test_cancel_request() {
+        let mut rpc = build_rpc();
+        let peer_id = PeerId::random();
+
+        // A request queued before the peer connects is dropped.
+        rpc.dialing_peer(peer_id);
+        rpc.send_request(peer_id, 1, OutboundRequest::Ping(Ping { data: 1 }));
+        rpc.send_request(peer_id, 2, OutboundRequest::Ping(Ping { data: 2 }));
+        rpc.cancel_request(peer_id, 1);
+        assert_eq!(rpc.pending_requests.get(&peer_id).map(Vec::len), Some(1));
+        assert!(rpc.events.is_empty());
+
+        // Once connected, the remaining request is sent. Cancelling it before the handler picks it
+        // up removes it, and the handler is told to drop it.
+        connect(&mut rpc, peer_id, ConnectionId::new(1));
+        assert_eq!(rpc.events.len(), 1);
+        rpc.cancel_request(peer_id, 2);
+        assert_eq!(rpc.events.len(), 1);
+        assert!(matches!(
+            rpc.events.pop_front(),
+            Some(NetworkBehaviourAction::NotifyHandler {
+                event: RPCSend::Cancel(2),
+                ..
+            })
+        ));
+
+        // Cancelling an unknown request to a disconnected peer is a no-op.
+        rpc.cancel_request(PeerId::random(), 3);
+        assert!(rpc.events.is_empty());
+    }
+
+    /// A cancel is sent to every connection of the peer, since the request may be running on any
+    /// of them.
+    #[tokio::test]
+    async fn test_cancel_request_notifies_all_connections() {
+        let mut rpc = build_rpc();
+        let peer_id = PeerId::random();
+        connect(&mut rpc, peer_id, ConnectionId::new(1));
+        rpc.on_swarm_event(FromSwarm::ConnectionEstablished(ConnectionEstablished {
+            peer_id,
+            connection_id: ConnectionId::new(2),
+            endpoint: &endpoint(),
+            failed_addresses: &[],
+            other_established: 1,
+        }));
+
+        rpc.cancel_request(peer_id, 1);
+        let notified = rpc
+            .events
+            .drain(..)
+            .map(|event| match event {
+                NetworkBehaviourAction::NotifyHandler {
+                    handler: NotifyHandler::One(conn_id),
+                    event: RPCSend::Cancel(1),
+                    ..
+                } => conn_id,
+                _ => panic!("Expected a cancel for a single connection"),
+            })
+            .collect::<HashSet<_>>();
+        assert_eq!(
+            notified,
+            HashSet::from([ConnectionId::new(1), ConnectionId::new(2)])
+        );
+    }
+
+    /// Goodbyes are counted per reason, separately for the ones we send and receive.
+    #[tokio::test]
+    async fn test_goodbye_metrics() {
+        let goodbyes = |direction: &str| {
+            metrics::get_int_counter(&metrics::TOTAL_RPC_GOODBYES, &["banned", direction])
+                .map_or(0, |counter| counter.get())
+        };
+        let mut rpc = build_rpc();
+        let peer_id = PeerId::random();
+        let (sent, received) = (goodbyes("sent"), goodbyes("received"));
+
+        connect(&mut rpc, peer_id, ConnectionId::new(1));
+        rpc.shutdown(peer_id, 0, GoodbyeReason::Banned);
+        assert_eq!(goodbyes("sent"), sent + 1);
+        assert_eq!(goodbyes("received"), received);
+
+        rpc.inject_event(
+            peer_id,
+            ConnectionId::new(1),
+            Ok(RPCReceived::Request(
+                SubstreamId::new(0),
+                InboundRequest::Goodbye(GoodbyeReason::Banned),
+            )),
+        );
+        assert_eq!(goodbyes("sent"), sent + 1);
+        assert_eq!(goodbyes("received"), received + 1);
+    }
+
+    /// Inbound substreams are given the configured time to negotiate.
+    #[tokio::test]
+    async fn test_inbound_negotiation_timeout() {
+        let mut rpc = build_rpc();
+        assert_eq!(
+            *rpc.new_handler().listen_protocol().timeout(),
+            Duration::from_secs(INBOUND_NEGOTIATION_TIMEOUT)
+        );
+
+        rpc.network_params.inbound_negotiation_timeout = Duration::from_secs(3);
+        assert_eq!(
+            *rpc.new_handler().listen_protocol().timeout(),
+            Duration::from_secs(3)
+        );
+    }
+}
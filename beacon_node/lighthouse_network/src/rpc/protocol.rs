@@ -121,6 +121,9 @@ const TTFB_TIMEOUT: u64 = 5;
 /// The number of seconds to wait for the first bytes of a request once a protocol has been
 /// established before the stream is terminated.
 const REQUEST_TIMEOUT: u64 = 15;
+/// The number of seconds an inbound substream has to negotiate a protocol and deliver its request
+/// before it is dropped.
+pub const INBOUND_NEGOTIATION_TIMEOUT: u64 = 10;
 
 /// Returns the maximum bytes that can be sent across the RPC.
 pub fn max_rpc_size(fork_context: &ForkContext) -> usize {
@@ -395,7 +398,7 @@ where
     TSpec: EthSpec,
 {
     type Output = InboundOutput<TSocket, TSpec>;
-    type Error = RPCError;
+    type Error = (Protocol, RPCError);
     type Future = BoxFuture<'static, Result<Self::Output, Self::Error>>;
 
     fn upgrade_inbound(self, socket: TSocket, protocol: ProtocolId) -> Self::Future {
@@ -428,10 +431,10 @@ where
                     )
                     .await
                     {
-                        Err(e) => Err(RPCError::from(e)),
+                        Err(e) => Err((protocol_name, RPCError::from(e))),
                         Ok((Some(Ok(request)), stream)) => Ok((request, stream)),
-                        Ok((Some(Err(e)), _)) => Err(e),
-                        Ok((None, _)) => Err(RPCError::IncompleteStream),
+                        Ok((Some(Err(e)), _)) => Err((protocol_name, e)),
+                        Ok((None, _)) => Err((protocol_name, RPCError::IncompleteStream)),
                     }
                 }
             }
@@ -614,3 +617,78 @@ impl RPCError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use snap::write::FrameEncoder;
+    use std::io::Write;
+    use types::Slot;
+
+    type Spec = MainnetEthSpec;
+
+    /// Runs the inbound upgrade for the given protocol over a substream containing `bytes`.
+    async fn upgrade_inbound(
+        protocol: Protocol,
+        bytes: Vec<u8>,
+    ) -> Result<InboundRequest<Spec>, (Protocol, RPCError)> {
+        let fork_context = Arc::new(ForkContext::new::<Spec>(
+            Slot::new(0),
+            Hash256::zero(),
+            &Spec::default_spec(),
+        ));
+        let rpc_protocol = RPCProtocol::<Spec> {
+            max_rpc_size: max_rpc_size(&fork_context),
+            fork_context,
+            enable_light_client_server: false,
+            phantom: PhantomData,
+        };
+        let protocol_id = ProtocolId::new(protocol, Version::V1, Encoding::SSZSnappy);
+        rpc_protocol
+            .upgrade_inbound(futures::io::Cursor::new(bytes), protocol_id)
+            .await
+            .map(|(request, _stream)| request)
+    }
+
+    /// Test that a request that can't be read reports the error along with the protocol, so the
+    /// handler can report it.
+    #[tokio::test]
+    async fn test_upgrade_inbound_errors() {
+        // A substream closed without any bytes is an incomplete stream.
+        assert_eq!(
+            upgrade_inbound(Protocol::Status, vec![]).await.unwrap_err(),
+            (Protocol::Status, RPCError::IncompleteStream)
+        );
+
+        // A length prefix that is too large is invalid data.
+        let (protocol, error) = upgrade_inbound(Protocol::Status, vec![0xe8, 0x07])
+            .await
+            .unwrap_err();
+        assert_eq!(protocol, Protocol::Status);
+        assert!(matches!(error, RPCError::InvalidData(_)));
+
+        // A payload that is not a list of roots can't be ssz decoded.
+        let mut writer = FrameEncoder::new(vec![33]);
+        writer.write_all(&[0; 33]).unwrap();
+        let (protocol, error) =
+            upgrade_inbound(Protocol::BlocksByRoot, writer.into_inner().unwrap())
+                .await
+                .unwrap_err();
+        assert_eq!(protocol, Protocol::BlocksByRoot);
+        assert!(matches!(error, RPCError::SSZDecodeError(_)));
+
+        // Garbage following a valid length prefix is invalid data, not a transport error. The
+        // snappy frame is complete but its checksum is corrupted.
+        let status_len = <StatusMessage as Encode>::ssz_fixed_len();
+        let mut writer = FrameEncoder::new(vec![status_len as u8]);
+        writer.write_all(&vec![0; status_len]).unwrap();
+        let mut garbage = writer.into_inner().unwrap();
+        // Skip the length prefix, the stream identifier chunk and the data chunk's header.
+        garbage[15] ^= 0xff;
+        let (protocol, error) = upgrade_inbound(Protocol::Status, garbage)
+            .await
+            .unwrap_err();
+        assert_eq!(protocol, Protocol::Status);
+        assert!(matches!(error, RPCError::InvalidData(_)));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::rpc::config::InboundRateLimiterConfig;
 use crate::rpc::Protocol;
 use fnv::FnvHashMap;
 use libp2p::PeerId;
@@ -232,6 +233,30 @@ impl<T: EthSpec> RateLimiterItem for super::OutboundRequest<T> {
     }
 }
 impl RPCRateLimiter {
+    /// Creates a new [`RPCRateLimiter`] for inbound requests based on configuration values.
+    pub fn new_with_config(config: InboundRateLimiterConfig) -> Result<Self, &'static str> {
+        // Destructure to make sure every configuration value is used.
+        let InboundRateLimiterConfig {
+            ping_quota,
+            meta_data_quota,
+            status_quota,
+            goodbye_quota,
+            blocks_by_range_quota,
+            blocks_by_root_quota,
+            light_client_bootstrap_quota,
+        } = config;
+
+        Self::builder()
+            .set_quota(Protocol::Ping, ping_quota)
+            .set_quota(Protocol::MetaData, meta_data_quota)
+            .set_quota(Protocol::Status, status_quota)
+            .set_quota(Protocol::Goodbye, goodbye_quota)
+            .set_quota(Protocol::BlocksByRange, blocks_by_range_quota)
+            .set_quota(Protocol::BlocksByRoot, blocks_by_root_quota)
+            .set_quota(Protocol::LightClientBootstrap, light_client_bootstrap_quota)
+            .build()
+    }
+
     /// Get a builder instance.
     pub fn builder() -> RPCRateLimiterBuilder {
         RPCRateLimiterBuilder::default()
@@ -362,8 +387,12 @@ impl<Key: Hash + Eq + Clone> Limiter<Key> {
 
 #[cfg(test)]
 mod tests {
-    use crate::rpc::rate_limiter::{Limiter, Quota};
+    use crate::rpc::config::InboundRateLimiterConfig;
+    use crate::rpc::rate_limiter::{Limiter, Quota, RPCRateLimiter, RateLimitedErr};
+    use crate::rpc::{InboundRequest, Ping, StatusMessage};
+    use libp2p::PeerId;
     use std::time::Duration;
+    use types::{Epoch, Hash256, MainnetEthSpec, Slot};
 
     #[test]
     fn it_works_a() {
@@ -430,4 +459,37 @@ mod tests {
             .allows(Duration::from_secs_f32(0.4), &key, 1)
             .is_err());
     }
+
+    #[tokio::test]
+    async fn test_flooding_peer_is_throttled() {
+        let mut limiter =
+            RPCRateLimiter::new_with_config(InboundRateLimiterConfig::default()).unwrap();
+        let peer_id = PeerId::random();
+        let other_peer_id = PeerId::random();
+
+        // Flood the limiter with pings from a single peer. Only the quota is let through.
+        let ping = InboundRequest::<MainnetEthSpec>::Ping(Ping { data: 0 });
+        let allowed = (0..100)
+            .filter(|_| limiter.allows(&peer_id, &ping).is_ok())
+            .count();
+        assert_eq!(
+            allowed as u64,
+            InboundRateLimiterConfig::DEFAULT_PING_QUOTA.max_tokens
+        );
+        assert!(matches!(
+            limiter.allows(&peer_id, &ping),
+            Err(RateLimitedErr::TooSoon(_))
+        ));
+
+        // Other protocols of the same peer and the same protocol of other peers are unaffected.
+        let status = InboundRequest::<MainnetEthSpec>::Status(StatusMessage {
+            fork_digest: [0; 4],
+            finalized_root: Hash256::zero(),
+            finalized_epoch: Epoch::new(0),
+            head_root: Hash256::zero(),
+            head_slot: Slot::new(0),
+        });
+        assert!(limiter.allows(&peer_id, &status).is_ok());
+        assert!(limiter.allows(&other_peer_id, &ping).is_ok());
+    }
 }
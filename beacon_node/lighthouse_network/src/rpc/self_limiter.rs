@@ -181,6 +181,57 @@ impl<Id: ReqId, TSpec: EthSpec> SelfRateLimiter<Id, TSpec> {
         }
     }
 
+    /// Informs the limiter that a peer has disconnected. This removes any pending requests and
+    /// returns their IDs.
+    pub fn peer_disconnected(&mut self, peer_id: PeerId) -> Vec<(Id, Protocol)> {
+        // It's not ideal to iterate this map, but the key is (PeerId, Protocol) and this map
+        // should never really be large. So we iterate for simplicity.
+        let mut failed_requests = Vec::new();
+        self.delayed_requests
+            .retain(|(map_peer_id, protocol), queue| {
+                if map_peer_id == &peer_id {
+                    // NOTE: Currently cannot remove entries from the DelayQueue, we will just let
+                    // them expire and ignore them.
+                    for message in queue {
+                        failed_requests.push((message.request_id, *protocol))
+                    }
+                    // Remove the entry
+                    false
+                } else {
+                    // Keep the entry
+                    true
+                }
+            });
+        failed_requests
+    }
+
+    /// Removes a request of the peer waiting to be sent. Returns true if the request was found.
+    pub fn cancel(&mut self, peer_id: PeerId, request_id: Id) -> bool {
+        let mut cancelled = false;
+        self.delayed_requests
+            .retain(|(map_peer_id, _protocol), queue| {
+                if map_peer_id == &peer_id {
+                    let queued = queue.len();
+                    queue.retain(|request| request.request_id != request_id);
+                    cancelled |= queue.len() != queued;
+                    // Remove the entry if no requests remain
+                    !queue.is_empty()
+                } else {
+                    // Keep the entry
+                    true
+                }
+            });
+        let ready = self.ready_requests.len();
+        self.ready_requests.retain(|event| {
+            !matches!(event, BehaviourAction::NotifyHandler {
+                    peer_id: p,
+                    event: RPCSend::Request(id, _),
+                    ..
+                } if *p == peer_id && *id == request_id)
+        });
+        cancelled || self.ready_requests.len() != ready
+    }
+
     pub fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<BehaviourAction<Id, TSpec>> {
         // First check the requests that were self rate limited, since those might add events to
         // the queue. Also do this this before rate limiter prunning to avoid removing and
@@ -200,3 +251,54 @@ impl<Id: ReqId, TSpec: EthSpec> SelfRateLimiter<Id, TSpec> {
         Poll::Pending
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::rpc::config::OutboundRateLimiterConfig;
+    use crate::rpc::rate_limiter::Quota;
+    use crate::rpc::self_limiter::SelfRateLimiter;
+    use crate::rpc::{OutboundRequest, Ping, Protocol};
+    use libp2p::PeerId;
+    use types::MainnetEthSpec;
+
+    /// Test that `peer_disconnected` drops and returns every request queued for the peer.
+    #[tokio::test]
+    async fn test_peer_disconnected() {
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let config = OutboundRateLimiterConfig {
+            ping_quota: Quota::n_every(1, 2),
+            ..Default::default()
+        };
+        let mut limiter: SelfRateLimiter<u64, MainnetEthSpec> =
+            SelfRateLimiter::new(config, log).unwrap();
+        let peer_id = PeerId::random();
+        let other_peer_id = PeerId::random();
+
+        for i in 1..=5 {
+            let _ = limiter.allows(peer_id, i, OutboundRequest::Ping(Ping { data: i }));
+        }
+        for i in 6..=7 {
+            let _ = limiter.allows(other_peer_id, i, OutboundRequest::Ping(Ping { data: i }));
+        }
+
+        // The first request of each peer is sent straight away, the rest are queued.
+        let failed = limiter.peer_disconnected(peer_id);
+        assert_eq!(
+            failed,
+            (2..=5).map(|i| (i, Protocol::Ping)).collect::<Vec<_>>()
+        );
+        assert!(limiter
+            .delayed_requests
+            .get(&(peer_id, Protocol::Ping))
+            .is_none());
+
+        // Requests for other peers are left untouched.
+        assert_eq!(
+            limiter
+                .delayed_requests
+                .get(&(other_peer_id, Protocol::Ping))
+                .map(|queue| queue.len()),
+            Some(1)
+        );
+    }
+}
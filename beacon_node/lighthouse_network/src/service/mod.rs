@@ -266,7 +266,13 @@ impl<AppReqId: ReqId, TSpec: EthSpec> Network<AppReqId, TSpec> {
         let eth2_rpc = RPC::new(
             ctx.fork_context.clone(),
             config.enable_light_client_server,
+            config.inbound_rate_limiter_config.clone(),
             config.outbound_rate_limiter_config.clone(),
+            NetworkParams {
+                resp_timeout: config.resp_timeout,
+                slow_response_threshold: config.slow_response_threshold,
+                inbound_negotiation_timeout: config.inbound_negotiation_timeout,
+            },
             log.clone(),
         );
 
@@ -747,7 +753,19 @@ impl<AppReqId: ReqId, TSpec: EthSpec> Network<AppReqId, TSpec> {
     /* Eth2 RPC behaviour functions */
 
     /// Send a request to a peer over RPC.
+    ///
+    /// Requests for a peer that is being dialed are sent once it connects. If the peer is
+    /// neither connected nor being dialed, the request fails with `NetworkEvent::RPCFailed`.
     pub fn send_request(&mut self, peer_id: PeerId, request_id: AppReqId, request: Request) {
+        let dialing = self
+            .network_globals
+            .peers
+            .read()
+            .peer_info(&peer_id)
+            .map_or(false, |info| info.is_dialing());
+        if dialing {
+            self.eth2_rpc_mut().dialing_peer(peer_id);
+        }
         self.eth2_rpc_mut().send_request(
             peer_id,
             RequestId::Application(request_id),
@@ -755,6 +773,15 @@ impl<AppReqId: ReqId, TSpec: EthSpec> Network<AppReqId, TSpec> {
         )
     }
 
+    /// Cancel a request sent to a peer over RPC. No further responses are reported for it.
+    ///
+    /// The id must uniquely identify the request among the ones in flight to the peer, otherwise
+    /// every request sharing it is cancelled.
+    pub fn cancel_request(&mut self, peer_id: PeerId, request_id: AppReqId) {
+        self.eth2_rpc_mut()
+            .cancel_request(peer_id, RequestId::Application(request_id))
+    }
+
     /// Send a successful response to a peer over RPC.
     pub fn send_response(&mut self, peer_id: PeerId, id: PeerRequestId, response: Response<TSpec>) {
         self.eth2_rpc_mut()
@@ -1138,6 +1165,15 @@ impl<AppReqId: ReqId, TSpec: EthSpec> Network<AppReqId, TSpec> {
         let peer_id = event.peer_id;
 
         if !self.peer_manager().is_connected(&peer_id) {
+            // Requests that never reached the peer are still reported to the application.
+            if let Err(HandlerErr::Outbound {
+                id: RequestId::Application(id),
+                error: RPCError::Disconnected,
+                ..
+            }) = &event.event
+            {
+                return Some(NetworkEvent::RPCFailed { peer_id, id: *id });
+            }
             debug!(
                 self.log,
                 "Ignoring rpc message of disconnecting peer";
@@ -1224,31 +1260,27 @@ impl<AppReqId: ReqId, TSpec: EthSpec> Network<AppReqId, TSpec> {
                         Some(event)
                     }
                     InboundRequest::BlocksByRange(req) => {
-                        let methods::OldBlocksByRangeRequest {
-                            start_slot,
-                            mut count,
-                            step,
-                        } = req;
-                        // Still disconnect the peer if the request is naughty.
-                        if step == 0 {
-                            self.peer_manager_mut().handle_rpc_error(
-                                &peer_id,
-                                Protocol::BlocksByRange,
-                                &RPCError::InvalidData(
-                                    "Blocks by range with 0 step parameter".into(),
-                                ),
-                                ConnectionDirection::Incoming,
-                            );
-                            return None;
-                        }
-                        // return just one block in case the step parameter is used. https://github.com/ethereum/consensus-specs/pull/2856
-                        if step > 1 {
-                            count = 1;
-                        }
+                        // Still disconnect the peer if the request is naughty. Unlike a request
+                        // that could not be decoded, a 0 step is never sent by an honest peer.
+                        let req = match req.to_blocks_by_range_request() {
+                            Some(req) => req,
+                            None => {
+                                debug!(self.log, "Blocks by range with 0 step parameter";
+                                    "peer_id" => %peer_id);
+                                self.peer_manager_mut().report_peer(
+                                    &peer_id,
+                                    PeerAction::Fatal,
+                                    ReportSource::RPC,
+                                    None,
+                                    "blocks_by_range_zero_step",
+                                );
+                                return None;
+                            }
+                        };
                         let event = self.build_request(
                             peer_request_id,
                             peer_id,
-                            Request::BlocksByRange(BlocksByRangeRequest { start_slot, count }),
+                            Request::BlocksByRange(req),
                         );
                         Some(event)
                     }
@@ -94,9 +94,21 @@ pub async fn build_libp2p_instance(
     boot_nodes: Vec<Enr>,
     log: slog::Logger,
     fork_name: ForkName,
+) -> Libp2pInstance {
+    build_libp2p_instance_with_config(rt, boot_nodes, log, fork_name, |_| {}).await
+}
+
+// Builds a libp2p instance whose default test config is adjusted by `configure`.
+pub async fn build_libp2p_instance_with_config(
+    rt: Weak<Runtime>,
+    boot_nodes: Vec<Enr>,
+    log: slog::Logger,
+    fork_name: ForkName,
+    configure: impl Fn(&mut NetworkConfig),
 ) -> Libp2pInstance {
     let port = unused_tcp4_port().unwrap();
-    let config = build_config(port, boot_nodes);
+    let mut config = build_config(port, boot_nodes);
+    configure(&mut config);
     // launch libp2p service
 
     let (signal, exit) = exit_future::signal();
@@ -160,12 +172,27 @@ pub async fn build_node_pair(
     rt: Weak<Runtime>,
     log: &slog::Logger,
     fork_name: ForkName,
+) -> (Libp2pInstance, Libp2pInstance) {
+    build_node_pair_with_config(rt, log, fork_name, |_| {}).await
+}
+
+// Constructs a pair of nodes as `build_node_pair` does, adjusting the config of both with
+// `configure`.
+#[allow(dead_code)]
+pub async fn build_node_pair_with_config(
+    rt: Weak<Runtime>,
+    log: &slog::Logger,
+    fork_name: ForkName,
+    configure: impl Fn(&mut NetworkConfig),
 ) -> (Libp2pInstance, Libp2pInstance) {
     let sender_log = log.new(o!("who" => "sender"));
     let receiver_log = log.new(o!("who" => "receiver"));
 
-    let mut sender = build_libp2p_instance(rt.clone(), vec![], sender_log, fork_name).await;
-    let mut receiver = build_libp2p_instance(rt, vec![], receiver_log, fork_name).await;
+    let mut sender =
+        build_libp2p_instance_with_config(rt.clone(), vec![], sender_log, fork_name, &configure)
+            .await;
+    let mut receiver =
+        build_libp2p_instance_with_config(rt, vec![], receiver_log, fork_name, &configure).await;
 
     let receiver_multiaddr = receiver.local_enr().multiaddr()[1].clone();
 
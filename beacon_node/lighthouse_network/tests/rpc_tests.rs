@@ -1,17 +1,34 @@
 #![cfg(test)]
+use futures::future::BoxFuture;
+use futures::{AsyncRead, AsyncReadExt, AsyncWrite, FutureExt, StreamExt};
+use libp2p::core::connection::ConnectionId;
+use libp2p::core::upgrade::{DeniedUpgrade, OutboundUpgrade, UpgradeInfo};
+use libp2p::identity::Keypair;
+use libp2p::swarm::behaviour::{ConnectionEstablished, FromSwarm};
+use libp2p::swarm::{
+    NetworkBehaviour, NetworkBehaviourAction, NotifyHandler, OneShotHandler, OneShotHandlerConfig,
+    PollParameters, SubstreamProtocol, SwarmBuilder, SwarmEvent,
+};
+use libp2p::PeerId;
 use lighthouse_network::rpc::methods::*;
-use lighthouse_network::{rpc::max_rpc_size, NetworkEvent, ReportSource, Request, Response};
+use lighthouse_network::service::utils::build_transport;
+use lighthouse_network::{
+    metrics, rpc::max_rpc_size, NetworkEvent, ReportSource, Request, Response,
+};
 use slog::{debug, warn, Level};
 use ssz::Encode;
 use ssz_types::VariableList;
+use std::collections::VecDeque;
 use std::sync::Arc;
-use std::time::Duration;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
 use tokio::time::sleep;
 use types::{
     BeaconBlock, BeaconBlockAltair, BeaconBlockBase, BeaconBlockMerge, EmptyBlock, Epoch, EthSpec,
     ForkContext, ForkName, Hash256, MinimalEthSpec, Signature, SignedBeaconBlock, Slot,
 };
+use void::Void;
 
 mod common;
 
@@ -135,6 +152,271 @@ fn test_status_rpc() {
     })
 }
 
+// Tests that a STATUS request and its response are reflected in the RPC metrics
+#[test]
+#[allow(clippy::single_match)]
+fn test_status_rpc_metrics() {
+    // set up the logging. The level and enabled logging or not
+    let log_level = Level::Debug;
+    let enable_logging = false;
+
+    let rt = Arc::new(Runtime::new().unwrap());
+
+    let log = common::build_log(log_level, enable_logging);
+
+    // The metrics are global and shared with the other tests, so only compare against the
+    // values seen before the request is sent.
+    let requests_sent = || {
+        metrics::get_int_counter(&metrics::TOTAL_RPC_REQUESTS_SENT, &["status"])
+            .map_or(0, |counter| counter.get())
+    };
+    let responses_received = || {
+        metrics::get_int_counter(&metrics::TOTAL_RPC_RESPONSES_RECEIVED, &["status"])
+            .map_or(0, |counter| counter.get())
+    };
+    let response_times = || {
+        metrics::get_histogram(&metrics::RPC_RESPONSE_TIME, &["status"])
+            .map_or(0, |histogram| histogram.get_sample_count())
+    };
+
+    rt.block_on(async {
+        // get sender/receiver
+        let (mut sender, mut receiver) =
+            common::build_node_pair(Arc::downgrade(&rt), &log, ForkName::Base).await;
+
+        // Dummy STATUS RPC message
+        let status_message = StatusMessage {
+            fork_digest: [0; 4],
+            finalized_root: Hash256::from_low_u64_be(0),
+            finalized_epoch: Epoch::new(1),
+            head_root: Hash256::from_low_u64_be(0),
+            head_slot: Slot::new(1),
+        };
+        let rpc_request = Request::Status(status_message.clone());
+        let rpc_response = Response::Status(status_message);
+
+        // build the sender future
+        let sender_future = async {
+            let mut before = None;
+            loop {
+                match sender.next_event().await {
+                    NetworkEvent::PeerConnectedOutgoing(peer_id) => {
+                        // Send a STATUS message
+                        debug!(log, "Sending RPC");
+                        before = Some((requests_sent(), responses_received(), response_times()));
+                        sender.send_request(peer_id, 10, rpc_request.clone());
+                    }
+                    NetworkEvent::ResponseReceived { id: 10, .. } => {
+                        debug!(log, "Sender Received");
+                        let (sent, received, times) = before.expect("request sent");
+                        assert!(requests_sent() > sent);
+                        assert!(responses_received() > received);
+                        assert!(response_times() > times);
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        // build the receiver future
+        let receiver_future = async {
+            loop {
+                match receiver.next_event().await {
+                    NetworkEvent::RequestReceived {
+                        peer_id,
+                        id,
+                        request,
+                    } => {
+                        if request == rpc_request {
+                            // send the response
+                            debug!(log, "Receiver Received");
+                            receiver.send_response(peer_id, id, rpc_response.clone());
+                        }
+                    }
+                    _ => {} // Ignore other events
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = sender_future => {}
+            _ = receiver_future => {}
+            _ = sleep(Duration::from_secs(30)) => {
+                panic!("Future timed out");
+            }
+        }
+    })
+}
+
+// Tests several concurrent STATUS RPC messages to the same peer
+#[test]
+#[allow(clippy::single_match)]
+fn test_status_rpc_concurrent_requests() {
+    // set up the logging. The level and enabled logging or not
+    let log_level = Level::Debug;
+    let enable_logging = false;
+
+    let rt = Arc::new(Runtime::new().unwrap());
+
+    let log = common::build_log(log_level, enable_logging);
+
+    // Each request carries its id in the head slot, so responses can be matched to requests.
+    let status_message = |id: usize| StatusMessage {
+        fork_digest: [0; 4],
+        finalized_root: Hash256::from_low_u64_be(0),
+        finalized_epoch: Epoch::new(1),
+        head_root: Hash256::from_low_u64_be(0),
+        head_slot: Slot::new(id as u64),
+    };
+
+    let request_ids = [10, 11, 12];
+
+    rt.block_on(async {
+        // get sender/receiver
+        let (mut sender, mut receiver) =
+            common::build_node_pair(Arc::downgrade(&rt), &log, ForkName::Base).await;
+
+        // build the sender future
+        let sender_future = async {
+            let mut received = Vec::new();
+            loop {
+                match sender.next_event().await {
+                    NetworkEvent::PeerConnectedOutgoing(peer_id) => {
+                        // Send all STATUS messages without waiting for any response
+                        debug!(log, "Sending RPCs");
+                        for id in request_ids {
+                            sender.send_request(peer_id, id, Request::Status(status_message(id)));
+                        }
+                    }
+                    NetworkEvent::ResponseReceived {
+                        peer_id: _,
+                        id,
+                        response,
+                    } => {
+                        // Should receive the response matching the request id
+                        debug!(log, "Sender Received"; "id" => id);
+                        assert_eq!(response, Response::Status(status_message(id)));
+                        assert!(!received.contains(&id));
+                        received.push(id);
+                        if received.len() == request_ids.len() {
+                            received.sort_unstable();
+                            assert_eq!(received, request_ids);
+                            debug!(log, "Sender Completed");
+                            return;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        // build the receiver future
+        let receiver_future = async {
+            loop {
+                match receiver.next_event().await {
+                    NetworkEvent::RequestReceived {
+                        peer_id,
+                        id,
+                        request: Request::Status(status),
+                    } => {
+                        // echo the status back to the sender
+                        debug!(log, "Receiver Received");
+                        receiver.send_response(peer_id, id, Response::Status(status));
+                    }
+                    _ => {} // Ignore other events
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = sender_future => {}
+            _ = receiver_future => {}
+            _ = sleep(Duration::from_secs(30)) => {
+                panic!("Future timed out");
+            }
+        }
+    })
+}
+
+// Tests that a request the peer never answers times out and is reported as failed
+#[test]
+#[allow(clippy::single_match)]
+fn test_status_rpc_response_timeout() {
+    // set up the logging. The level and enabled logging or not
+    let log_level = Level::Debug;
+    let enable_logging = false;
+
+    let rt = Arc::new(Runtime::new().unwrap());
+
+    let log = common::build_log(log_level, enable_logging);
+
+    rt.block_on(async {
+        // get sender/receiver, with a short response timeout
+        let (mut sender, mut receiver) = common::build_node_pair_with_config(
+            Arc::downgrade(&rt),
+            &log,
+            ForkName::Base,
+            |config| config.resp_timeout = Duration::from_secs(1),
+        )
+        .await;
+
+        // Dummy STATUS RPC message
+        let rpc_request = Request::Status(StatusMessage {
+            fork_digest: [0; 4],
+            finalized_root: Hash256::from_low_u64_be(0),
+            finalized_epoch: Epoch::new(1),
+            head_root: Hash256::from_low_u64_be(0),
+            head_slot: Slot::new(1),
+        });
+
+        // build the sender future
+        let sender_future = async {
+            loop {
+                match sender.next_event().await {
+                    NetworkEvent::PeerConnectedOutgoing(peer_id) => {
+                        // Send a STATUS message
+                        debug!(log, "Sending RPC");
+                        sender.send_request(peer_id, 10, rpc_request.clone());
+                    }
+                    NetworkEvent::ResponseReceived { .. } => {
+                        panic!("Should not receive a response");
+                    }
+                    NetworkEvent::RPCFailed { peer_id: _, id } => {
+                        // The unanswered request should fail once it times out
+                        assert_eq!(id, 10);
+                        debug!(log, "Sender Completed");
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        // build the receiver future, which never answers the request
+        let receiver_future = async {
+            loop {
+                match receiver.next_event().await {
+                    NetworkEvent::RequestReceived { request, .. } => {
+                        if request == rpc_request {
+                            debug!(log, "Receiver Received. Not responding");
+                        }
+                    }
+                    _ => {} // Ignore other events
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = sender_future => {}
+            _ = receiver_future => {}
+            _ = sleep(Duration::from_secs(30)) => {
+                panic!("Future timed out");
+            }
+        }
+    })
+}
+
 // Tests a streamed BlocksByRange RPC Message
 #[test]
 #[allow(clippy::single_match)]
@@ -473,6 +755,142 @@ fn test_blocks_by_range_chunked_rpc_terminates_correctly() {
     })
 }
 
+// Tests that no further chunks of a streamed BlocksByRange RPC Message are received once the
+// request is cancelled
+#[test]
+fn test_blocks_by_range_chunked_rpc_cancelled() {
+    // set up the logging. The level and enabled logging or not
+    let log_level = Level::Debug;
+    let enable_logging = false;
+
+    let messages_to_send = 10;
+
+    let log = common::build_log(log_level, enable_logging);
+
+    let rt = Arc::new(Runtime::new().unwrap());
+
+    rt.block_on(async {
+        // get sender/receiver
+        let (mut sender, mut receiver) =
+            common::build_node_pair(Arc::downgrade(&rt), &log, ForkName::Base).await;
+
+        // BlocksByRange Request
+        let rpc_request = Request::BlocksByRange(BlocksByRangeRequest {
+            start_slot: 0,
+            count: messages_to_send,
+        });
+
+        // BlocksByRange Response
+        let spec = E::default_spec();
+        let empty_block = BeaconBlock::empty(&spec);
+        let empty_signed = SignedBeaconBlock::from_block(empty_block, Signature::empty());
+        let rpc_response = Response::BlocksByRange(Some(Arc::new(empty_signed)));
+
+        let request_id = messages_to_send as usize;
+        // build the sender future
+        let sender_future = async {
+            let mut messages_received = 0;
+            loop {
+                match sender.next_event().await {
+                    NetworkEvent::PeerConnectedOutgoing(peer_id) => {
+                        // Send a BlocksByRange request
+                        debug!(log, "Sending RPC");
+                        sender.send_request(peer_id, request_id, rpc_request.clone());
+                    }
+                    NetworkEvent::ResponseReceived {
+                        peer_id,
+                        id,
+                        response,
+                    } => {
+                        debug!(log, "Sender received a response");
+                        assert_eq!(id, request_id);
+                        match response {
+                            Response::BlocksByRange(Some(_)) => {
+                                messages_received += 1;
+                                // Only the chunk received before cancelling is expected
+                                assert_eq!(messages_received, 1);
+                                sender.cancel_request(peer_id, request_id);
+                            }
+                            _ => panic!("Response received after the request was cancelled"),
+                        }
+                    }
+                    NetworkEvent::RPCFailed { .. } => {
+                        panic!("Cancelled requests should not be reported as failed")
+                    }
+                    _ => {} // Ignore other behaviour events
+                }
+            }
+        };
+
+        // determine messages to send (PeerId, RequestId). If some, indicates we still need to send
+        // messages
+        let mut message_info = None;
+        // the number of messages we've sent
+        let mut messages_sent = 0;
+        // the number of rounds passed since the last message was sent
+        let mut idle_rounds = 0;
+        let receiver_future = async {
+            loop {
+                // this future either drives the sending/receiving or times out allowing messages to be
+                // sent in the timeout
+                match futures::future::select(
+                    Box::pin(receiver.next_event()),
+                    Box::pin(tokio::time::sleep(Duration::from_secs(1))),
+                )
+                .await
+                {
+                    futures::future::Either::Left((
+                        NetworkEvent::RequestReceived {
+                            peer_id,
+                            id,
+                            request,
+                        },
+                        _,
+                    )) => {
+                        if request == rpc_request {
+                            // send the response
+                            warn!(log, "Receiver got request");
+                            message_info = Some((peer_id, id));
+                        }
+                    }
+                    futures::future::Either::Right((_, _)) => {} // The timeout hit, send messages if required
+                    _ => continue,
+                }
+
+                // if we need to send messages send them here. This will happen after a delay
+                if let Some((peer_id, stream_id)) = message_info {
+                    if messages_sent < messages_to_send {
+                        messages_sent += 1;
+                        receiver.send_response(peer_id, stream_id, rpc_response.clone());
+                        debug!(log, "Sending message {}", messages_sent);
+                        if messages_sent == messages_to_send {
+                            receiver.send_response(
+                                peer_id,
+                                stream_id,
+                                Response::BlocksByRange(None),
+                            );
+                        }
+                    } else {
+                        // give the sender time to receive any further responses
+                        idle_rounds += 1;
+                        if idle_rounds == 2 {
+                            return;
+                        }
+                    }
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = sender_future => {}
+            _ = receiver_future => {}
+            _ = sleep(Duration::from_secs(30)) => {
+                panic!("Future timed out");
+            }
+        }
+    })
+}
+
 // Tests an empty response to a BlocksByRange RPC Message
 #[test]
 #[allow(clippy::single_match)]
@@ -896,3 +1314,182 @@ fn test_goodbye_rpc() {
         }
     })
 }
+
+/// Outbound upgrade that opens a `Status` substream but never sends the request. It resolves
+/// once the remote drops the substream.
+#[derive(Debug)]
+struct StalledRequest;
+
+impl UpgradeInfo for StalledRequest {
+    type Info = &'static [u8];
+    type InfoIter = std::iter::Once<Self::Info>;
+
+    fn protocol_info(&self) -> Self::InfoIter {
+        std::iter::once(b"/eth2/beacon_chain/req/status/1/ssz_snappy")
+    }
+}
+
+impl<S> OutboundUpgrade<S> for StalledRequest
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Output = ();
+    type Error = Void;
+    type Future = BoxFuture<'static, Result<(), Void>>;
+
+    fn upgrade_outbound(self, mut socket: S, _: Self::Info) -> Self::Future {
+        async move {
+            // The remote never writes to this substream, the read only returns once it is closed.
+            let _ = socket.read(&mut [0u8; 1]).await;
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+#[derive(Debug)]
+struct SubstreamClosed;
+
+impl From<()> for SubstreamClosed {
+    fn from(_: ()) -> Self {
+        SubstreamClosed
+    }
+}
+
+impl From<Void> for SubstreamClosed {
+    fn from(v: Void) -> Self {
+        void::unreachable(v)
+    }
+}
+
+/// Behaviour that opens a single stalled request on every new connection.
+#[derive(Default)]
+struct StallingBehaviour {
+    events: VecDeque<
+        NetworkBehaviourAction<
+            SubstreamClosed,
+            OneShotHandler<DeniedUpgrade, StalledRequest, SubstreamClosed>,
+        >,
+    >,
+}
+
+impl NetworkBehaviour for StallingBehaviour {
+    type ConnectionHandler = OneShotHandler<DeniedUpgrade, StalledRequest, SubstreamClosed>;
+    type OutEvent = SubstreamClosed;
+
+    fn new_handler(&mut self) -> Self::ConnectionHandler {
+        OneShotHandler::new(
+            SubstreamProtocol::new(DeniedUpgrade, ()),
+            OneShotHandlerConfig::default(),
+        )
+    }
+
+    fn inject_event(&mut self, _: PeerId, _: ConnectionId, event: SubstreamClosed) {
+        self.events
+            .push_back(NetworkBehaviourAction::GenerateEvent(event));
+    }
+
+    fn on_swarm_event(&mut self, event: FromSwarm<Self::ConnectionHandler>) {
+        if let FromSwarm::ConnectionEstablished(ConnectionEstablished {
+            peer_id,
+            connection_id,
+            ..
+        }) = event
+        {
+            self.events
+                .push_back(NetworkBehaviourAction::NotifyHandler {
+                    peer_id,
+                    handler: NotifyHandler::One(connection_id),
+                    event: StalledRequest,
+                });
+        }
+    }
+
+    fn poll(
+        &mut self,
+        _: &mut Context,
+        _: &mut impl PollParameters,
+    ) -> Poll<NetworkBehaviourAction<Self::OutEvent, Self::ConnectionHandler>> {
+        match self.events.pop_front() {
+            Some(event) => Poll::Ready(event),
+            None => Poll::Pending,
+        }
+    }
+}
+
+// Tests that an inbound substream which never delivers its request is dropped once the
+// configured inbound negotiation timeout expires, well before the TTFB timeout.
+#[test]
+fn test_stalled_inbound_negotiation_times_out() {
+    // set up the logging. The level and enabled logging or not
+    let log_level = Level::Debug;
+    let enable_logging = false;
+
+    let log = common::build_log(log_level, enable_logging);
+
+    let negotiation_timeout = Duration::from_secs(1);
+
+    let rt = Arc::new(Runtime::new().unwrap());
+    rt.block_on(async {
+        let mut receiver = common::build_libp2p_instance_with_config(
+            Arc::downgrade(&rt),
+            vec![],
+            log.clone(),
+            ForkName::Base,
+            |config| config.inbound_negotiation_timeout = negotiation_timeout,
+        )
+        .await;
+        let receiver_multiaddr = receiver.local_enr().multiaddr()[1].clone();
+
+        // let the receiver set up its listener
+        loop {
+            if let NetworkEvent::NewListenAddr(_) = receiver.next_event().await {
+                break;
+            }
+        }
+
+        let keypair = Keypair::generate_secp256k1();
+        let peer_id = PeerId::from(keypair.public());
+        let (transport, _) = build_transport(keypair).unwrap();
+        let mut stalling =
+            SwarmBuilder::without_executor(transport, StallingBehaviour::default(), peer_id)
+                .build();
+        stalling.dial(receiver_multiaddr).unwrap();
+
+        // build the stalling peer future
+        let stalling_future = async {
+            let mut connected_at = None;
+            loop {
+                match stalling.select_next_some().await {
+                    SwarmEvent::ConnectionEstablished { .. } => {
+                        connected_at = Some(Instant::now());
+                    }
+                    SwarmEvent::Behaviour(SubstreamClosed) => {
+                        let elapsed = connected_at.expect("connected").elapsed();
+                        debug!(log, "Stalled substream closed"; "elapsed" => ?elapsed);
+                        // The TTFB timeout would only close the substream after 5 seconds.
+                        assert!(elapsed >= negotiation_timeout);
+                        assert!(elapsed < Duration::from_secs(4));
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+        };
+
+        // build the receiver future
+        let receiver_future = async {
+            loop {
+                receiver.next_event().await;
+            }
+        };
+
+        tokio::select! {
+            _ = stalling_future => {}
+            _ = receiver_future => {}
+            _ = sleep(Duration::from_secs(10)) => {
+                panic!("Future timed out");
+            }
+        }
+    })
+}
@@ -5,14 +5,15 @@ use crate::beacon_processor::work_reprocessing_queue::{
     QUEUED_ATTESTATION_DELAY, QUEUED_RPC_BLOCK_DELAY,
 };
 use crate::beacon_processor::*;
-use crate::{service::NetworkMessage, sync::SyncMessage};
+use crate::{service::NetworkMessage, status::ToStatusMessage, sync::SyncMessage};
 use beacon_chain::test_utils::{
     AttestationStrategy, BeaconChainHarness, BlockStrategy, EphemeralHarnessType,
 };
-use beacon_chain::{BeaconChain, ChainConfig, MAXIMUM_GOSSIP_CLOCK_DISPARITY};
+use beacon_chain::{BeaconChain, ChainConfig, WhenSlotSkipped, MAXIMUM_GOSSIP_CLOCK_DISPARITY};
 use lighthouse_network::{
     discv5::enr::{CombinedKey, EnrBuilder},
     rpc::methods::{MetaData, MetaDataV2},
+    rpc::{GoodbyeReason, StatusMessage},
     types::{EnrAttestationBitfield, EnrSyncCommitteeBitfield},
     MessageId, NetworkGlobals, PeerId,
 };
@@ -24,7 +25,7 @@ use std::time::Duration;
 use tokio::sync::mpsc;
 use types::{
     Attestation, AttesterSlashing, Epoch, EthSpec, MainnetEthSpec, ProposerSlashing,
-    SignedBeaconBlock, SignedVoluntaryExit, SubnetId,
+    SignedBeaconBlock, SignedVoluntaryExit, Slot, SubnetId,
 };
 
 type E = MainnetEthSpec;
@@ -53,8 +54,8 @@ struct TestRig {
     voluntary_exit: SignedVoluntaryExit,
     beacon_processor_tx: mpsc::Sender<WorkEvent<T>>,
     work_journal_rx: mpsc::Receiver<&'static str>,
-    _network_rx: mpsc::UnboundedReceiver<NetworkMessage<E>>,
-    _sync_rx: mpsc::UnboundedReceiver<SyncMessage<E>>,
+    network_rx: mpsc::UnboundedReceiver<NetworkMessage<E>>,
+    sync_rx: mpsc::UnboundedReceiver<SyncMessage<E>>,
     duplicate_cache: DuplicateCache,
     _harness: BeaconChainHarness<T>,
 }
@@ -164,12 +165,12 @@ impl TestRig {
 
         let chain = harness.chain.clone();
 
-        let (network_tx, _network_rx) = mpsc::unbounded_channel();
+        let (network_tx, network_rx) = mpsc::unbounded_channel();
 
         let log = harness.logger().clone();
 
         let (beacon_processor_tx, beacon_processor_rx) = mpsc::channel(MAX_WORK_EVENT_QUEUE_LEN);
-        let (sync_tx, _sync_rx) = mpsc::unbounded_channel();
+        let (sync_tx, sync_rx) = mpsc::unbounded_channel();
 
         // Default metadata
         let meta_data = MetaData::V2(MetaDataV2 {
@@ -218,8 +219,8 @@ impl TestRig {
             voluntary_exit,
             beacon_processor_tx,
             work_journal_rx,
-            _network_rx,
-            _sync_rx,
+            network_rx,
+            sync_rx,
             duplicate_cache,
             _harness: harness,
         }
@@ -349,6 +350,40 @@ impl TestRig {
             .unwrap();
     }
 
+    pub fn enqueue_status(&self, peer_id: PeerId, status: StatusMessage) {
+        self.beacon_processor_tx
+            .try_send(WorkEvent::status_message(peer_id, status))
+            .unwrap();
+    }
+
+    /// Processes a `Status` from a new peer and returns whether the peer was found relevant, i.e.
+    /// it was added to sync rather than disconnected.
+    pub async fn status_is_relevant(&mut self, status: StatusMessage) -> bool {
+        let peer_id = junk_peer_id();
+        self.enqueue_status(peer_id, status);
+        self.assert_event_journal(&[STATUS_PROCESSING, WORKER_FREED, NOTHING_TO_DO])
+            .await;
+
+        match (self.sync_rx.try_recv(), self.network_rx.try_recv()) {
+            (Ok(SyncMessage::AddPeer(added_peer_id, _)), Err(_)) => {
+                assert_eq!(added_peer_id, peer_id);
+                true
+            }
+            (
+                Err(_),
+                Ok(NetworkMessage::GoodbyePeer {
+                    peer_id: goodbye_peer_id,
+                    reason: GoodbyeReason::IrrelevantNetwork,
+                    ..
+                }),
+            ) => {
+                assert_eq!(goodbye_peer_id, peer_id);
+                false
+            }
+            _ => panic!("Status should either add the peer to sync or disconnect it"),
+        }
+    }
+
     /// Assert that the `BeaconProcessor` doesn't produce any events in the given `duration`.
     pub async fn assert_no_events_for(&mut self, duration: Duration) {
         tokio::select! {
@@ -933,3 +968,62 @@ async fn test_backfill_sync_processing_rate_limiting_disabled() {
     )
     .await;
 }
+
+/// Ensure that a `Status` whose head is before its own finalized block is rejected, while a head
+/// far behind the finalized epoch's start slot is accepted when the slots in between were skipped.
+#[tokio::test]
+async fn test_status_finalized_block_and_head_consistency() {
+    let mut rig = TestRig::new(LONG_CHAIN).await;
+    let local = rig.chain.status_message();
+    let chain = rig.chain.clone();
+    let block_root_at_slot = |slot: u64| {
+        chain
+            .block_root_at_slot(Slot::new(slot), WhenSlotSkipped::None)
+            .unwrap()
+            .unwrap()
+    };
+
+    // Our own status is valid.
+    assert!(rig.status_is_relevant(local.clone()).await);
+
+    // The status of a node at genesis is valid.
+    let genesis = StatusMessage {
+        fork_digest: local.fork_digest,
+        finalized_root: Hash256::zero(),
+        finalized_epoch: Epoch::new(0),
+        head_root: Hash256::zero(),
+        head_slot: Slot::new(0),
+    };
+    assert!(rig.status_is_relevant(genesis.clone()).await);
+
+    // All the slots from 9 up to the start of the finalized epoch were skipped, so the finalized
+    // block is also the head, more than an epoch before the finalized epoch's start slot.
+    let after_skipped_slots = StatusMessage {
+        finalized_root: block_root_at_slot(8),
+        finalized_epoch: Epoch::new(2),
+        head_root: block_root_at_slot(8),
+        head_slot: Slot::new(8),
+        ..genesis.clone()
+    };
+    assert!(rig.status_is_relevant(after_skipped_slots).await);
+
+    // A finalized block we don't know can't be checked against the head.
+    let unknown_finalized_block = StatusMessage {
+        finalized_root: Hash256::repeat_byte(1),
+        finalized_epoch: Epoch::new(2),
+        head_root: Hash256::repeat_byte(1),
+        head_slot: Slot::new(8),
+        ..genesis.clone()
+    };
+    assert!(rig.status_is_relevant(unknown_finalized_block).await);
+
+    // A head before the finalized block is inconsistent.
+    let inconsistent = StatusMessage {
+        finalized_root: block_root_at_slot(40),
+        finalized_epoch: Epoch::new(2),
+        head_root: block_root_at_slot(39),
+        head_slot: Slot::new(39),
+        ..genesis
+    };
+    assert!(!rig.status_is_relevant(inconsistent).await);
+}
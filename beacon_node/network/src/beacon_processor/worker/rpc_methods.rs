@@ -73,6 +73,16 @@ impl<T: BeaconChainTypes> Worker<T> {
                 hex::encode(local.fork_digest),
                 hex::encode(remote.fork_digest)
             ))
+        } else if remote.finalized_root != Hash256::zero()
+            && self
+                .chain
+                .get_blinded_block(&remote.finalized_root)?
+                .map_or(false, |block| remote.head_slot < block.slot())
+        {
+            // The remote's head is before its own finalized block. Only the finalized block's slot
+            // bounds the head, since the epoch boundary can be preceded by any number of skipped
+            // slots. Finalized blocks we don't know can't be checked.
+            Some("Finalized block ahead of head".to_string())
         } else if remote.head_slot
             > self
                 .chain
@@ -131,6 +141,16 @@ impl<T: BeaconChainTypes> Worker<T> {
         request_id: PeerRequestId,
         request: BlocksByRootRequest,
     ) {
+        if request.block_roots.is_empty() {
+            debug!(self.log, "Peer sent an empty BlocksByRoot request"; "peer" => %peer_id);
+            return self.send_error_response(
+                peer_id,
+                RPCResponseErrorCode::InvalidRequest,
+                "No block roots requested".into(),
+                request_id,
+            );
+        }
+
         let requested_blocks = request.block_roots.len();
         let mut block_stream = match self
             .chain
@@ -326,7 +346,7 @@ impl<T: BeaconChainTypes> Worker<T> {
         // Pick out the required blocks, ignoring skip-slots.
         let mut last_block_root = None;
         let maybe_block_roots = process_results(forwards_block_root_iter, |iter| {
-            iter.take_while(|(_, slot)| slot.as_u64() < req.start_slot.saturating_add(req.count))
+            iter.take_while(|(_, slot)| slot.as_u64() < req.end_slot())
                 // map skip slots to None
                 .map(|(root, _)| {
                     let result = if Some(root) == last_block_root {
@@ -365,7 +385,7 @@ impl<T: BeaconChainTypes> Worker<T> {
                             // Due to skip slots, blocks could be out of the range, we ensure they
                             // are in the range before sending
                             if block.slot() >= req.start_slot
-                                && block.slot() < req.start_slot + req.count
+                                && block.slot() < req.end_slot()
                             {
                                 blocks_sent += 1;
                                 self.send_network_message(NetworkMessage::SendResponse {
@@ -42,12 +42,14 @@ const METRIC_UPDATE_INTERVAL: u64 = 5;
 const SUBSCRIBE_DELAY_SLOTS: u64 = 2;
 /// Delay after a fork where we unsubscribe from pre-fork topics.
 const UNSUBSCRIBE_DELAY_EPOCHS: u64 = 2;
+/// The time (in milliseconds) given to the goodbyes sent to our peers on shutdown to be delivered.
+const SHUTDOWN_GOODBYE_TIMEOUT: u64 = 500;
 /// Size of the queue for validator subnet subscriptions. The number is chosen so that we may be
 /// able to run tens of thousands of validators on one BN.
 const VALIDATOR_SUBSCRIPTION_MESSAGE_QUEUE_SIZE: usize = 65_536;
 
 /// Application level requests sent to the network.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RequestId {
     Sync(SyncId),
     Router,
@@ -66,6 +68,9 @@ pub enum NetworkMessage<T: EthSpec> {
         request: Request,
         request_id: RequestId,
     },
+    /// Cancel a request sent to a peer. No further responses are received for it. Only sync
+    /// requests can be cancelled, since router requests all share the same id.
+    CancelRequest { peer_id: PeerId, request_id: SyncId },
     /// Send a successful Response to the libp2p service.
     SendResponse {
         peer_id: PeerId,
@@ -405,11 +410,19 @@ impl<T: BeaconChainTypes> NetworkService<T> {
 
     fn spawn_service(mut self, executor: task_executor::TaskExecutor) {
         let mut shutdown_sender = executor.shutdown_sender();
+        let exit = executor.exit();
 
         // spawn on the current executor
         let service_fut = async move {
+            tokio::pin!(exit);
             loop {
                 tokio::select! {
+                    // the node is shutting down, say goodbye to our peers before terminating
+                    _ = &mut exit => {
+                        self.goodbye_connected_peers().await;
+                        break;
+                    }
+
                     _ = self.metrics_update.tick(), if self.metrics_enabled => {
                         // update various network metrics
                         metrics::update_gossip_metrics::<T::EthSpec>(
@@ -461,7 +474,48 @@ impl<T: BeaconChainTypes> NetworkService<T> {
                 metrics::update_bandwidth_metrics(self.libp2p.bandwidth.clone());
             }
         };
-        executor.spawn(service_fut, "network");
+        executor.spawn_without_exit(service_fut, "network");
+    }
+
+    /// Sends a Goodbye to every connected peer through the peer manager and keeps driving the
+    /// swarm until they are disconnected, for at most `SHUTDOWN_GOODBYE_TIMEOUT`.
+    async fn goodbye_connected_peers(&mut self) {
+        let peers = self
+            .network_globals
+            .peers
+            .read()
+            .connected_peer_ids()
+            .cloned()
+            .collect::<Vec<_>>();
+        debug!(self.log, "Sending goodbye to connected peers"; "peers" => peers.len());
+        for peer_id in peers.iter() {
+            self.libp2p.goodbye_peer(
+                peer_id,
+                GoodbyeReason::ClientShutdown,
+                ReportSource::PeerManager,
+            );
+        }
+
+        let network_globals = self.network_globals.clone();
+        let remaining_peers = || {
+            let peer_db = network_globals.peers.read();
+            peers
+                .iter()
+                .filter(|peer_id| peer_db.is_connected_or_disconnecting(peer_id))
+                .count()
+        };
+        let libp2p = &mut self.libp2p;
+        let disconnected = async {
+            while remaining_peers() > 0 {
+                // events are no longer handled once the node is shutting down
+                let _ = libp2p.next_event().await;
+            }
+        };
+        let timeout = Duration::from_millis(SHUTDOWN_GOODBYE_TIMEOUT);
+        if tokio::time::timeout(timeout, disconnected).await.is_err() {
+            debug!(self.log, "Peers did not disconnect before shutdown";
+                "peers" => remaining_peers());
+        }
     }
 
     /// Handle an event received from the network.
@@ -584,6 +638,13 @@ impl<T: BeaconChainTypes> NetworkService<T> {
             } => {
                 self.libp2p.send_request(peer_id, request_id, request);
             }
+            NetworkMessage::CancelRequest {
+                peer_id,
+                request_id,
+            } => {
+                self.libp2p
+                    .cancel_request(peer_id, RequestId::Sync(request_id));
+            }
             NetworkMessage::SendResponse {
                 peer_id,
                 response,
@@ -166,6 +166,22 @@ impl<T: BeaconChainTypes> SyncNetworkContext<T> {
         }
     }
 
+    /// Cancels a blocks by range request of the range sync algorithm. No further responses are
+    /// received for it.
+    pub fn cancel_range_request(&mut self, peer_id: PeerId, request_id: Id) {
+        trace!(
+            self.log,
+            "Cancelling BlocksByRange Request";
+            "peer" => %peer_id,
+            "id" => request_id,
+        );
+        self.range_requests.remove(&request_id);
+        let _ = self.send_network_msg(NetworkMessage::CancelRequest {
+            peer_id,
+            request_id: SyncRequestId::RangeSync { id: request_id },
+        });
+    }
+
     /// Received a blocks by range response.
     pub fn backfill_sync_response(&mut self, request_id: Id, remove: bool) -> Option<BatchId> {
         if remove {
@@ -204,6 +204,7 @@ where
         request_id: Id,
         beacon_block: Option<Arc<SignedBeaconBlock<T::EthSpec>>>,
     ) {
+        let is_stream_termination = beacon_block.is_none();
         // check if this chunk removes the chain
         match self.chains.call_by_id(chain_id, |chain| {
             chain.on_block_response(network, batch_id, &peer_id, request_id, beacon_block)
@@ -220,7 +221,11 @@ where
                 }
             }
             Err(_) => {
-                trace!(self.log, "BlocksByRange response for removed chain"; "chain" => chain_id)
+                trace!(self.log, "BlocksByRange response for removed chain"; "chain" => chain_id);
+                // No one is interested in the rest of the response, stop the peer from sending it.
+                if !is_stream_termination {
+                    network.cancel_range_request(peer_id, request_id);
+                }
             }
         }
     }
@@ -390,7 +395,7 @@ mod tests {
     use std::collections::HashSet;
     use std::sync::Arc;
     use store::MemoryStore;
-    use types::{Hash256, MinimalEthSpec as E};
+    use types::{BeaconBlock, EthSpec, Hash256, MinimalEthSpec as E, Signature, SignedBeaconBlock};
 
     #[derive(Debug)]
     struct FakeStorage {
@@ -643,6 +648,53 @@ mod tests {
         range.assert_state(RangeSyncType::Finalized);
     }
 
+    #[test]
+    fn response_for_removed_chain_cancels_request() {
+        let (mut rig, mut range) = range(false);
+
+        // Get a peer with an advanced head
+        let (peer_id, local_info, remote_info) = rig.head_peer();
+        range.add_peer(&mut rig.cx, local_info, peer_id, remote_info);
+        let ((chain_id, batch_id), id) = match rig.grab_request(&peer_id).0 {
+            RequestId::Sync(crate::sync::manager::RequestId::RangeSync { id }) => {
+                (rig.cx.range_sync_response(id, false).unwrap(), id)
+            }
+            other => panic!("unexpected request {:?}", other),
+        };
+
+        // Remove the chain by disconnecting its only peer.
+        range.remove_peer(&mut rig.cx, &peer_id);
+
+        // A block for the removed chain arriving cancels the rest of the request.
+        let block = SignedBeaconBlock::from_block(
+            BeaconBlock::empty(&E::default_spec()),
+            Signature::empty(),
+        );
+        range.blocks_by_range_response(
+            &mut rig.cx,
+            peer_id,
+            chain_id,
+            batch_id,
+            id,
+            Some(Arc::new(block)),
+        );
+        match rig.network_rx.try_recv() {
+            Ok(NetworkMessage::CancelRequest {
+                peer_id: cancelled_peer,
+                request_id: crate::sync::manager::RequestId::RangeSync { id: cancelled_id },
+            }) => {
+                assert_eq!(cancelled_peer, peer_id);
+                assert_eq!(cancelled_id, id);
+            }
+            other => panic!("Expected the request to be cancelled, got {:?}", other),
+        }
+        assert!(rig.cx.range_sync_response(id, false).is_none());
+
+        // The end of the stream needs no cancelling.
+        range.blocks_by_range_response(&mut rig.cx, peer_id, chain_id, batch_id, id, None);
+        assert!(rig.network_rx.try_recv().is_err());
+    }
+
     #[test]
     fn state_update_while_purging() {
         // NOTE: this is a regression test.
@@ -254,6 +254,20 @@ pub fn cli_app<'a, 'b>() -> App<'a, 'b> {
                 .help("Lighthouse by default does not discover private IP addresses. Set this flag to enable connection attempts to local addresses.")
                 .takes_value(false),
         )
+        .arg(
+            Arg::with_name("inbound-rate-limiter")
+            .long("inbound-rate-limiter")
+            .help(
+                "Configures the inbound rate limiter (requests received by this node).\
+                \
+                Rate limit quotas per protocol can be set in the form of \
+                <protocol_name>:<tokens>/<time_in_seconds>. To set quotas for multiple protocols, \
+                separate them by ';'. Protocols that are not present in the configuration use \
+                the default quotas."
+            )
+            .takes_value(true)
+            .hidden(true)
+        )
         .arg(
             Arg::with_name("self-limiter")
             .long("self-limiter")
@@ -263,8 +277,7 @@ pub fn cli_app<'a, 'b>() -> App<'a, 'b> {
                 Rate limit quotas per protocol can be set in the form of \
                 <protocol_name>:<tokens>/<time_in_seconds>. To set quotas for multiple protocols, \
                 separate them by ';'. If the self rate limiter is enabled and a protocol is not \
-                present in the configuration, the quotas used for the inbound rate limiter will be \
-                used."
+                present in the configuration, the default quotas are used."
             )
             .min_values(0)
             .hidden(true)
@@ -1435,6 +1435,23 @@ fn empty_self_limiter_flag() {
         });
 }
 #[test]
+fn inbound_rate_limiter_flag() {
+    CommandLineTest::new()
+        .flag(
+            "inbound-rate-limiter",
+            Some("ping:1/10;beacon_blocks_by_range:512/10"),
+        )
+        .run_with_zero_port()
+        .with_config(|config| {
+            assert_eq!(
+                config.network.inbound_rate_limiter_config,
+                "ping:1/10;beacon_blocks_by_range:512/10"
+                    .parse::<lighthouse_network::rpc::config::InboundRateLimiterConfig>()
+                    .unwrap()
+            )
+        });
+}
+#[test]
 fn http_allow_origin_flag() {
     CommandLineTest::new()
         .flag("http-allow-origin", Some("127.0.0.99"))